@@ -0,0 +1,69 @@
+// ABOUTME: Arrange command implementation
+// ABOUTME: Tiles, cascades or groups sticky windows by color on a given screen
+
+use std::collections::HashSet;
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    filesystem::{self, frame, StickyColor},
+    layout::{self, Arrangement},
+    query::{Filter, SearchQuery, Term},
+    tags, Result, StickyError,
+};
+
+pub fn run(
+    arrangement: Arrangement,
+    screen: &str,
+    color: Option<&str>,
+    tag: Option<&str>,
+) -> Result<()> {
+    let screen = frame::parse_screen(screen).ok_or_else(|| {
+        StickyError::InvalidInput(format!(
+            "Invalid screen '{}': expected WIDTHxHEIGHT or X,Y,WIDTHxHEIGHT",
+            screen
+        ))
+    })?;
+
+    let mut config = Config::load()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
+    let db = Database::create(&config.database_path)?;
+    let stickies_path = filesystem::stickies_dir()?;
+
+    let mut query = SearchQuery::default();
+    if let Some(c) = color {
+        let color: StickyColor = c.parse().map_err(StickyError::InvalidInput)?;
+        query.push(Term::Filter(Filter::Color(color)));
+    }
+    if let Some(t) = tag {
+        let tag = tags::normalize_tag(t)
+            .ok_or_else(|| StickyError::InvalidInput(format!("Invalid tag '{}'", t)))?;
+        query.push(Term::Filter(Filter::Tag(tag)));
+    }
+
+    // Filters need the database; without them every local sticky is arranged
+    let only: Option<HashSet<String>> = if query.is_empty() {
+        None
+    } else {
+        Some(
+            db.search_query(&query, None, 0)?
+                .into_iter()
+                .map(|hit| hit.sticky.uuid)
+                .collect(),
+        )
+    };
+
+    let moved = layout::arrange_stickies(
+        &db,
+        &stickies_path,
+        &machine_id,
+        arrangement,
+        &screen,
+        only.as_ref(),
+    )?;
+
+    println!("Arranged {} stickies", moved);
+    if moved > 0 {
+        println!("Run `sticky hup` to reload Stickies.app with the new layout");
+    }
+    Ok(())
+}
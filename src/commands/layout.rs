@@ -0,0 +1,99 @@
+// ABOUTME: Layout command implementation
+// ABOUTME: Saves, restores, lists and deletes named sticky window arrangements
+
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    filesystem::{self, frame},
+    layout, Result, StickyError,
+};
+
+pub fn save(name: &str) -> Result<()> {
+    let config = Config::load()?;
+    config.ensure_dirs()?;
+    let db = Database::create(&config.database_path)?;
+    let stickies_path = filesystem::stickies_dir()?;
+
+    let entries = layout::snapshot(&stickies_path)?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    db.save_layout(name, &entries, now)?;
+
+    println!("Saved layout '{}' ({} stickies)", name, entries.len());
+    Ok(())
+}
+
+pub fn restore(name: &str, clamp: Option<&str>) -> Result<()> {
+    let screen = clamp
+        .map(|s| {
+            frame::parse_screen(s).ok_or_else(|| {
+                StickyError::InvalidInput(format!(
+                    "Invalid screen '{}': expected WIDTHxHEIGHT or X,Y,WIDTHxHEIGHT",
+                    s
+                ))
+            })
+        })
+        .transpose()?;
+
+    let mut config = Config::load()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
+    let db = Database::create(&config.database_path)?;
+    let stickies_path = filesystem::stickies_dir()?;
+
+    let entries = db.get_layout(name)?;
+    if entries.is_empty() {
+        return Err(StickyError::NotFound(format!(
+            "Layout '{}' not found",
+            name
+        )));
+    }
+
+    let restored = layout::restore(&db, &stickies_path, &machine_id, &entries, screen.as_ref())?;
+
+    println!(
+        "Restored layout '{}' ({} of {} stickies)",
+        name,
+        restored,
+        entries.len()
+    );
+    println!("Run `sticky hup` to reload Stickies.app with the new layout");
+    Ok(())
+}
+
+pub fn list() -> Result<()> {
+    let config = Config::load()?;
+    let db = Database::create(&config.database_path)?;
+
+    let layouts = db.list_layouts()?;
+    if layouts.is_empty() {
+        println!("No saved layouts");
+        return Ok(());
+    }
+
+    for summary in layouts {
+        println!(
+            "{} ({} stickies, saved {})",
+            summary.name, summary.sticky_count, summary.saved_at
+        );
+    }
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    let config = Config::load()?;
+    let db = Database::create(&config.database_path)?;
+
+    if !db.delete_layout(name)? {
+        return Err(StickyError::NotFound(format!(
+            "Layout '{}' not found",
+            name
+        )));
+    }
+
+    println!("Deleted layout '{}'", name);
+    Ok(())
+}
@@ -1,23 +1,49 @@
 // ABOUTME: List command implementation
-// ABOUTME: Lists all stickies with optional color filtering
+// ABOUTME: Lists all stickies with optional color and tag filtering
 
-use sticky_situation::{config::Config, database::Database, Result};
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    filesystem::{StickyColor, StickyMetadata},
+    query::{Filter, SearchQuery, Term},
+    tags, Result, StickyError,
+};
+
+pub fn run(color: Option<&str>, tag: Option<&str>) -> Result<()> {
+    let mut query = SearchQuery::default();
+    if let Some(c) = color {
+        let color: StickyColor = c.parse().map_err(StickyError::InvalidInput)?;
+        query.push(Term::Filter(Filter::Color(color)));
+    }
+    if let Some(t) = tag {
+        let tag = tags::normalize_tag(t)
+            .ok_or_else(|| StickyError::InvalidInput(format!("Invalid tag '{}'", t)))?;
+        query.push(Term::Filter(Filter::Tag(tag)));
+    }
 
-pub fn run(color: Option<&str>) -> Result<()> {
     let config = Config::load()?;
     let db = Database::create(&config.database_path)?;
 
-    // Use efficient single query instead of N+1 pattern
-    let stickies = match color {
-        Some(c) => db.get_stickies_by_color(c)?,
-        None => db.get_all_stickies()?,
-    };
+    // Filters are applied in a single query instead of an N+1 pattern
+    let stickies: Vec<_> = db
+        .search_query(&query, None, 0)?
+        .into_iter()
+        .map(|hit| hit.sticky)
+        .collect();
 
     if stickies.is_empty() {
+        let mut description = Vec::new();
         if let Some(c) = color {
-            println!("No {} stickies found", c);
-        } else {
+            description.push(c.to_string());
+        }
+        if let Some(t) = tag {
+            description.push(format!("#{}", t.trim_start_matches('#')));
+        }
+
+        if description.is_empty() {
             println!("No stickies found");
+        } else {
+            println!("No {} stickies found", description.join(" "));
         }
         return Ok(());
     }
@@ -34,6 +60,13 @@ pub fn run(color: Option<&str>) -> Result<()> {
         println!("UUID: {}", sticky.uuid);
         println!("Color: {}", sticky.color);
         println!("Preview: {}", preview);
+        if let Ok(metadata) = StickyMetadata::from_bytes(&sticky.plist_metadata) {
+            let frame = metadata.frame;
+            println!(
+                "Frame: {}, {} ({} x {})",
+                frame.x, frame.y, frame.width, frame.height
+            );
+        }
         println!("Modified: {}", sticky.modified_at);
         println!("---");
     }
@@ -0,0 +1,57 @@
+// ABOUTME: Merge command implementation
+// ABOUTME: Merges another sticky-situation database into the configured one
+
+use std::path::Path;
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    sync::{merge, SyncAction, SyncOptions},
+    Result,
+};
+
+pub fn run(other: &Path, dry_run: bool, verbose: bool) -> Result<()> {
+    let mut config = Config::load()?;
+    config.ensure_dirs()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
+    let db = Database::create(&config.database_path)?;
+
+    let options = SyncOptions {
+        dry_run,
+        machine_id,
+        ..Default::default()
+    };
+    let report = merge::merge_database(&db, other, &options)?;
+
+    if verbose {
+        for action in &report.actions {
+            match action {
+                SyncAction::NewOnFilesystem(uuid) => println!("Added: {}", uuid),
+                SyncAction::UpdateDatabase(uuid) => println!("Updated: {}", uuid),
+                SyncAction::UpdateLayout(uuid) => println!("Updated layout: {}", uuid),
+                _ => {}
+            }
+        }
+    }
+
+    for warning in &report.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    for conflict in &report.conflicts {
+        eprintln!("Conflict: {}", conflict);
+    }
+
+    if dry_run {
+        println!("Dry run: {} sticky/stickies would change", report.changed());
+    } else {
+        println!(
+            "Merged {} sticky/stickies from {}",
+            report.changed(),
+            other.display()
+        );
+        if report.changed() > 0 {
+            println!("Run `sticky sync` to write them to Stickies.app");
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,17 @@
 // ABOUTME: CLI command implementations
-// ABOUTME: Contains sync, new, search, list, show, hup, and config command handlers
+// ABOUTME: Contains sync, watch, merge, quarantine, arrange, new, search, list, show, set, tag, layout, hup, and config command handlers
 
+pub mod arrange;
 pub mod config;
 pub mod hup;
+pub mod layout;
 pub mod list;
+pub mod merge;
 pub mod new;
+pub mod quarantine;
 pub mod search;
+pub mod set;
 pub mod show;
 pub mod sync;
+pub mod tag;
+pub mod watch;
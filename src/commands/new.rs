@@ -1,77 +1,76 @@
 // ABOUTME: New command implementation
-// ABOUTME: Creates new sticky in filesystem and database, then reloads Stickies.app
+// ABOUTME: Creates new sticky with its bundle, state entry and database row, then reloads Stickies.app
 
-use std::process::Command;
+use std::collections::HashMap;
 use sticky_situation::{
     config::Config,
     database::{Database, Sticky},
-    filesystem::{self, rtfd::RtfdBundle},
+    filesystem::{self, app, plist, rtfd::RtfdBundle, StickyColor, StickyMetadata},
+    version_vector::VersionVector,
     Result, StickyError,
 };
 use uuid::Uuid;
 
-fn reload_stickies_app() -> Result<()> {
-    // Check if Stickies is running
-    let output = Command::new("pgrep").arg("Stickies").output()?;
-
-    if output.status.success() {
-        // Kill and restart for proper reload
-        Command::new("killall").arg("Stickies").status()?;
-        std::thread::sleep(std::time::Duration::from_millis(500));
-        Command::new("open").arg("-a").arg("Stickies").status()?;
-    } else {
-        // Not running, just launch it
-        Command::new("open").arg("-a").arg("Stickies").status()?;
-    }
-
-    Ok(())
-}
-
 pub fn run(text: Option<String>) -> Result<()> {
     let content = match text {
         Some(t) => t,
         None => {
             // TODO: Open $EDITOR for input
-            return Err(StickyError::Config("No text provided".into()));
+            return Err(StickyError::InvalidInput("No text provided".into()));
         }
     };
 
-    let config = Config::load()?;
+    let mut config = Config::load()?;
     config.ensure_dirs()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
 
     let db = Database::create(&config.database_path)?;
     let stickies_path = filesystem::stickies_dir()?;
 
+    // Stickies.app names bundles and state entries with upper-case UUIDs;
+    // the database keys them in lower case like sync does
     let uuid = Uuid::new_v4().to_string();
     let bundle = RtfdBundle::create_minimal(&content);
-    let rtfd_path = stickies_path.join(format!("{}.rtfd", uuid));
-
-    bundle.write(&rtfd_path)?;
-
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
-
-    let hostname = hostname::get()
-        .unwrap_or_else(|_| "unknown".into())
-        .to_string_lossy()
-        .to_string();
-
-    let sticky = Sticky {
-        uuid: uuid.clone(),
-        content_text: content.clone(),
-        rtf_data: bundle.rtf_data,
-        plist_metadata: vec![],
-        color: "yellow".to_string(),
-        modified_at: now,
-        created_at: now,
-        source_machine: hostname,
-    };
-
-    db.insert_sticky(&sticky)?;
-
-    reload_stickies_app()?;
+    let rtfd_path = filesystem::bundle_path(&stickies_path, &uuid.to_uppercase());
+    let metadata = StickyMetadata::new(StickyColor::Yellow.index());
+
+    // Launched afterwards even if it wasn't running, so the note shows up
+    app::while_quit(true, || {
+        bundle.write(&rtfd_path)?;
+
+        // With an entry in the state file Stickies.app opens the bundle under
+        // our UUID instead of importing it as a new note
+        let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+        let entry = HashMap::from([(uuid.clone(), metadata.raw.clone())]);
+        plist::update_stickies_state(&plist_path, &entry)?;
+
+        let now_ns = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i64;
+        let now = now_ns / 1_000_000_000;
+        RtfdBundle::set_modified_time_ns(&rtfd_path, now_ns)?;
+        let content_hash = bundle.content_hash();
+        let mut version_vector = VersionVector::new();
+        version_vector.increment(&machine_id);
+
+        let sticky = Sticky {
+            uuid: uuid.clone(),
+            content_text: content.clone(),
+            rtf_data: bundle.rtf_data,
+            plist_metadata: metadata.to_bytes()?,
+            is_floating: false,
+            color: metadata.color_name(),
+            modified_at: now,
+            created_at: now,
+            source_machine: machine_id,
+            modified_ns: now_ns,
+            content_hash,
+            version_vector,
+        };
+
+        db.insert_sticky(&sticky)
+    })?;
 
     println!("Created sticky: {}", uuid);
     println!("Content: {}", content);
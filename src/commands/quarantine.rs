@@ -0,0 +1,38 @@
+// ABOUTME: Quarantine command implementation
+// ABOUTME: Lists or clears stickies that this machine's syncs failed to read or write
+
+use sticky_situation::{config::Config, database::Database, Result};
+
+pub fn run(clear: bool) -> Result<()> {
+    let config = Config::load()?;
+    let db = Database::create(&config.database_path)?;
+    // Only reads (or clears) what syncs recorded, so it doesn't generate an ID;
+    // before the first sync with one, state is keyed by hostname
+    let machine_id = config.machine_id.unwrap_or_else(|| {
+        hostname::get()
+            .unwrap_or_else(|_| "unknown".into())
+            .to_string_lossy()
+            .to_string()
+    });
+
+    if clear {
+        let count = db.clear_quarantine(&machine_id)?;
+        println!("Cleared {} quarantined sticky/stickies", count);
+        return Ok(());
+    }
+
+    let entries = db.get_quarantine(&machine_id)?;
+    if entries.is_empty() {
+        println!("No quarantined stickies");
+        return Ok(());
+    }
+
+    for entry in entries {
+        println!(
+            "{} (failing since {}, last tried {})\n  {}",
+            entry.uuid, entry.first_seen, entry.last_seen, entry.error
+        );
+    }
+    println!("Fix the bundles and run `sticky sync` to retry them");
+    Ok(())
+}
@@ -1,40 +1,72 @@
 // ABOUTME: Search command implementation
-// ABOUTME: Full-text search using SQLite FTS5 with optional color filtering
+// ABOUTME: Full-text search with structured filters, ranked by relevance with paging
 
-use sticky_situation::{config::Config, database::Database, Result};
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    filesystem::StickyColor,
+    query::{Filter, SearchQuery, Term},
+    Result, StickyError,
+};
+
+pub fn run(
+    query: &str,
+    color: Option<&str>,
+    limit: usize,
+    offset: usize,
+    fts: bool,
+    full: bool,
+) -> Result<()> {
+    // Raw mode hands the query to FTS5 as-is; otherwise text is matched literally
+    let mut search = if fts {
+        SearchQuery::raw(query)
+    } else {
+        SearchQuery::parse(query)?
+    };
+    if let Some(c) = color {
+        let color: StickyColor = c.parse().map_err(StickyError::InvalidInput)?;
+        search.push(Term::Filter(Filter::Color(color)));
+    }
 
-pub fn run(query: &str, color: Option<&str>) -> Result<()> {
     let config = Config::load()?;
     let db = Database::create(&config.database_path)?;
 
-    let results = db.search(query)?;
+    let results = db.search_query(&search, Some(limit), offset)?;
 
-    let filtered: Vec<_> = results
-        .iter()
-        .filter(|s| color.is_none_or(|c| s.color == c))
-        .collect();
-
-    if filtered.is_empty() {
-        println!("No results found for '{}'", query);
+    if results.is_empty() {
+        if offset > 0 {
+            println!("No more results for '{}' (offset {})", query, offset);
+        } else {
+            println!("No results found for '{}'", query);
+        }
         return Ok(());
     }
 
-    println!("Found {} result(s):\n", filtered.len());
+    println!(
+        "Showing results {}-{} for '{}':\n",
+        offset + 1,
+        offset + results.len(),
+        query
+    );
 
-    for sticky in filtered {
-        println!("UUID: {}", sticky.uuid);
-        println!("Color: {}", sticky.color);
-
-        let preview = if sticky.content_text.len() > 100 {
-            format!("{}...", &sticky.content_text[..100])
+    for hit in &results {
+        println!("UUID: {}", hit.sticky.uuid);
+        println!("Color: {}", hit.sticky.color);
+        if full {
+            println!("Content:\n{}", hit.highlighted);
         } else {
-            sticky.content_text.clone()
-        };
-
-        println!("Preview: {}", preview);
-        println!("Modified: {}", sticky.modified_at);
+            println!("Match: {}", hit.snippet);
+        }
+        println!("Modified: {}", hit.sticky.modified_at);
         println!("---");
     }
 
+    if results.len() == limit {
+        println!(
+            "More results may be available: use --offset {}",
+            offset + limit
+        );
+    }
+
     Ok(())
 }
@@ -0,0 +1,56 @@
+// ABOUTME: Set command implementation
+// ABOUTME: Changes a sticky's window position and size in the database and Stickies state
+
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    filesystem::{self, frame},
+    layout, Result, StickyError,
+};
+
+pub fn run(uuid: &str, size: Option<&str>, position: Option<&str>) -> Result<()> {
+    if size.is_none() && position.is_none() {
+        return Err(StickyError::InvalidInput(
+            "Nothing to set: pass --size and/or --position".into(),
+        ));
+    }
+
+    let size = size
+        .map(|s| {
+            frame::parse_size(s).ok_or_else(|| {
+                StickyError::InvalidInput(format!("Invalid size '{}': expected WIDTHxHEIGHT", s))
+            })
+        })
+        .transpose()?;
+    let position = position
+        .map(|p| {
+            frame::parse_position(p).ok_or_else(|| {
+                StickyError::InvalidInput(format!("Invalid position '{}': expected X,Y", p))
+            })
+        })
+        .transpose()?;
+
+    let mut config = Config::load()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
+    let db = Database::create(&config.database_path)?;
+    let stickies_path = filesystem::stickies_dir().ok();
+
+    let (new_frame, local) = layout::set_frame(
+        &db,
+        stickies_path.as_deref(),
+        &machine_id,
+        uuid,
+        size,
+        position,
+    )?;
+    if local {
+        println!("Run `sticky hup` to reload Stickies.app with the new layout");
+    }
+
+    println!(
+        "{}: position {}, {}  size {} x {}",
+        uuid, new_frame.x, new_frame.y, new_frame.width, new_frame.height
+    );
+
+    Ok(())
+}
@@ -1,7 +1,12 @@
 // ABOUTME: Show command implementation
 // ABOUTME: Displays the full content of a specific sticky by UUID
 
-use sticky_situation::{config::Config, database::Database, Result, StickyError};
+use sticky_situation::{
+    config::Config,
+    database::Database,
+    filesystem::{StickyColor, StickyMetadata},
+    Result, StickyError,
+};
 
 pub fn run(uuid: &str) -> Result<()> {
     // Validate UUID format
@@ -15,10 +20,59 @@ pub fn run(uuid: &str) -> Result<()> {
     match db.get_sticky(uuid)? {
         Some(sticky) => {
             println!("UUID: {}", sticky.uuid);
-            println!("Color: {}", sticky.color);
+            match sticky
+                .color
+                .parse::<StickyColor>()
+                .ok()
+                .and_then(|c| c.hex())
+            {
+                Some(hex) => println!("Color: {} ({})", sticky.color, hex),
+                None => println!("Color: {}", sticky.color),
+            }
             println!("Source Machine: {}", sticky.source_machine);
             println!("Created: {}", sticky.created_at);
             println!("Modified: {}", sticky.modified_at);
+
+            if let Ok(metadata) = StickyMetadata::from_bytes(&sticky.plist_metadata) {
+                let frame = metadata.frame;
+                println!("Position: {}, {}", frame.x, frame.y);
+                println!("Size: {} x {}", frame.width, frame.height);
+                let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+                println!("Floating: {}", yes_no(metadata.is_floating));
+                println!("Collapsed: {}", yes_no(metadata.is_collapsed));
+                println!("Translucent: {}", yes_no(metadata.is_translucent));
+                if let Some(custom_color) = &metadata.custom_color {
+                    println!("Custom Color: {}", custom_color);
+                }
+                match (&metadata.font_name, metadata.font_size) {
+                    (Some(name), Some(size)) => println!("Font: {} {}pt", name, size),
+                    (Some(name), None) => println!("Font: {}", name),
+                    (None, Some(size)) => println!("Font: {}pt", size),
+                    (None, None) => {}
+                }
+            }
+
+            let attachments = db.get_attachment_info(&sticky.uuid)?;
+            if !attachments.is_empty() {
+                println!("Attachments:");
+                for attachment in attachments {
+                    let note = if attachment.stored {
+                        ""
+                    } else {
+                        " (over size limit, not synced)"
+                    };
+                    println!(
+                        "  {} ({}, {} bytes){}",
+                        attachment.filename, attachment.mime_type, attachment.size, note
+                    );
+                }
+            }
+
+            let tags = db.get_tags(&sticky.uuid)?;
+            if !tags.is_empty() {
+                println!("Tags: {}", tags.join(", "));
+            }
+
             println!("\nContent:");
             println!("{}", sticky.content_text);
 
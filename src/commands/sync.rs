@@ -1,123 +1,90 @@
 // ABOUTME: Sync command implementation
 // ABOUTME: Orchestrates bidirectional sync between Stickies.app and database
 
-use std::collections::HashMap;
 use sticky_situation::{
     config::Config,
-    database::{Database, Sticky},
-    filesystem::{self, plist, rtfd::RtfdBundle},
-    rtf,
-    sync::{SyncAction, SyncEngine},
-    Result,
+    database::Database,
+    filesystem,
+    sync::{runner, SyncAction, SyncOptions, SyncReport},
+    Result, StickyError,
 };
 
 pub fn run(dry_run: bool, verbose: bool) -> Result<()> {
-    let config = Config::load()?;
+    let mut config = Config::load()?;
     config.ensure_dirs()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
 
     let db = Database::create(&config.database_path)?;
     let stickies_path = filesystem::stickies_dir()?;
 
-    // Read filesystem state from .SavedStickiesState (the actual file Stickies.app uses)
-    let plist_path = stickies_path.join(".SavedStickiesState");
-    let metadata_map = plist::read_stickies_state(&plist_path)?;
-
-    let mut fs_uuids = Vec::new();
-    let mut fs_times = HashMap::new();
+    let options = SyncOptions {
+        dry_run,
+        machine_id,
+        max_attachment_bytes: config.max_attachment_bytes,
+        shared_dir: config.shared_dir.clone(),
+        conflict_log: config
+            .log_conflicts
+            .then(|| config.conflict_log_path.clone()),
+        ..Default::default()
+    };
+    let report = runner::run(&db, &stickies_path, &options)?;
+    print_report(&report, dry_run, verbose);
+
+    if report.failures.is_empty() {
+        Ok(())
+    } else {
+        Err(StickyError::SyncFailed(report.failures.len()))
+    }
+}
 
-    for uuid in metadata_map.keys() {
-        let rtfd_path = stickies_path.join(format!("{}.rtfd", uuid));
-        if rtfd_path.exists() {
-            fs_uuids.push(uuid.clone());
-            let mtime = RtfdBundle::modified_time(&rtfd_path)?;
-            fs_times.insert(uuid.clone(), mtime);
+/// Print what a sync did; shared with `sticky watch`
+pub fn print_report(report: &SyncReport, dry_run: bool, verbose: bool) {
+    if verbose {
+        for action in &report.actions {
+            match action {
+                SyncAction::NewOnFilesystem(uuid) => println!("New on filesystem: {}", uuid),
+                SyncAction::UpdateDatabase(uuid) => println!("Updating database: {}", uuid),
+                SyncAction::NewInDatabase(uuid) => println!("New in database: {}", uuid),
+                SyncAction::UpdateFilesystem(uuid) => println!("Updating filesystem: {}", uuid),
+                SyncAction::UpdateLayout(uuid) => println!("Updating layout: {}", uuid),
+                SyncAction::UpdateFilesystemLayout(uuid) => {
+                    println!("Updating layout on filesystem: {}", uuid)
+                }
+                SyncAction::Rekey { from, to } => println!("Re-keyed: {} -> {}", from, to),
+                SyncAction::NoChange(_) => {}
+            }
         }
     }
 
-    // Read database state
-    let db_uuids = db.get_all_uuids()?;
-    let mut db_times = HashMap::new();
+    for warning in &report.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    for conflict in &report.conflicts {
+        eprintln!("Conflict: {}", conflict);
+    }
 
-    for uuid in &db_uuids {
-        if let Some(sticky) = db.get_sticky(uuid)? {
-            db_times.insert(uuid.clone(), sticky.modified_at);
+    if !report.failures.is_empty() {
+        eprintln!("Failed to sync {} sticky/stickies:", report.failures.len());
+        for failure in &report.failures {
+            eprintln!("  {}: {}", failure.uuid, failure.error);
         }
     }
 
-    // Categorize actions
-    let actions = SyncEngine::categorize(&fs_uuids, &db_times, &fs_times);
-
-    // Execute actions
-    let hostname = hostname::get()
-        .unwrap_or_else(|_| "unknown".into())
-        .to_string_lossy()
-        .to_string();
-
-    for action in actions {
-        match action {
-            SyncAction::NewOnFilesystem(uuid) => {
-                if verbose {
-                    println!("New on filesystem: {}", uuid);
-                }
-
-                if !dry_run {
-                    let rtfd_path = stickies_path.join(format!("{}.rtfd", uuid));
-                    let bundle = RtfdBundle::read(&rtfd_path)?;
-                    let metadata = metadata_map.get(&uuid).unwrap();
-
-                    let sticky = Sticky {
-                        uuid: uuid.clone(),
-                        content_text: rtf::extract_text_from_bytes(&bundle.rtf_data),
-                        rtf_data: bundle.rtf_data,
-                        plist_metadata: vec![], // TODO: serialize metadata
-                        color: metadata.color_name().to_string(),
-                        modified_at: fs_times.get(&uuid).copied().unwrap_or(0),
-                        created_at: fs_times.get(&uuid).copied().unwrap_or(0),
-                        source_machine: hostname.clone(),
-                    };
-
-                    db.insert_sticky(&sticky)?;
-                }
-            }
-
-            SyncAction::UpdateDatabase(uuid) => {
-                if verbose {
-                    println!("Updating database: {}", uuid);
-                }
-
-                if !dry_run {
-                    let rtfd_path = stickies_path.join(format!("{}.rtfd", uuid));
-                    let bundle = RtfdBundle::read(&rtfd_path)?;
-                    let metadata = metadata_map.get(&uuid).unwrap();
-
-                    let sticky = Sticky {
-                        uuid: uuid.clone(),
-                        content_text: rtf::extract_text_from_bytes(&bundle.rtf_data),
-                        rtf_data: bundle.rtf_data,
-                        plist_metadata: vec![],
-                        color: metadata.color_name().to_string(),
-                        modified_at: fs_times.get(&uuid).copied().unwrap_or(0),
-                        created_at: fs_times.get(&uuid).copied().unwrap_or(0),
-                        source_machine: hostname.clone(),
-                    };
-
-                    db.insert_sticky(&sticky)?;
-                }
-            }
-
-            SyncAction::NoChange(_) => {
-                // Skip
-            }
-
-            _ => {
-                // TODO: Handle NewInDatabase and UpdateFilesystem in next task
-                if verbose {
-                    println!("Skipping action: {:?}", action);
-                }
-            }
+    if dry_run {
+        println!("Dry run: {} sticky/stickies would change", report.changed());
+    } else {
+        println!("Sync complete");
+
+        let wrote_filesystem = report.actions.iter().any(|a| {
+            matches!(
+                a,
+                SyncAction::NewInDatabase(_)
+                    | SyncAction::UpdateFilesystem(_)
+                    | SyncAction::UpdateFilesystemLayout(_)
+            )
+        });
+        if wrote_filesystem {
+            println!("Stickies were updated on disk; run `sticky hup` to reload Stickies.app");
         }
     }
-
-    println!("Sync complete");
-    Ok(())
 }
@@ -0,0 +1,48 @@
+// ABOUTME: Tag command implementation
+// ABOUTME: Adds or removes manual tags on a sticky and shows its current tags
+
+use sticky_situation::{config::Config, database::Database, tags, Result, StickyError};
+
+pub fn run(uuid: &str, changes: &[String]) -> Result<()> {
+    let config = Config::load()?;
+    let db = Database::create(&config.database_path)?;
+
+    if db.get_sticky(uuid)?.is_none() {
+        return Err(StickyError::NotFound(format!(
+            "Sticky with UUID {} not found",
+            uuid
+        )));
+    }
+
+    for change in changes {
+        let (add, name) = match change.strip_prefix('-') {
+            Some(name) => (false, name),
+            None => (true, change.strip_prefix('+').unwrap_or(change)),
+        };
+
+        let tag = tags::normalize_tag(name).ok_or_else(|| {
+            StickyError::InvalidInput(format!(
+                "Invalid tag '{}': tags contain only letters, digits, '_' and '-'",
+                name
+            ))
+        })?;
+
+        if add {
+            db.add_tag(uuid, &tag)?;
+        } else if db.remove_tag(uuid, &tag)? {
+            println!(
+                "Note: '{}' comes from #{} in the sticky's text and stays until the hashtag is removed",
+                tag, tag
+            );
+        }
+    }
+
+    let current = db.get_tags(uuid)?;
+    if current.is_empty() {
+        println!("{}: no tags", uuid);
+    } else {
+        println!("{}: {}", uuid, current.join(", "));
+    }
+
+    Ok(())
+}
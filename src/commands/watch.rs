@@ -0,0 +1,52 @@
+// ABOUTME: Watch command implementation
+// ABOUTME: Runs a sync after every burst of edits in Stickies.app or change to the database
+
+use std::time::Duration;
+use sticky_situation::{
+    config::Config,
+    filesystem,
+    sync::{watch, SyncOptions, WatchOptions},
+    Result,
+};
+
+pub fn run(debounce_ms: u64, poll_secs: u64, verbose: bool) -> Result<()> {
+    let mut config = Config::load()?;
+    config.ensure_dirs()?;
+    let machine_id = config.ensure_machine_id(&Config::config_path()?)?;
+
+    let stickies_path = filesystem::stickies_dir()?;
+    let sync_options = SyncOptions {
+        machine_id,
+        max_attachment_bytes: config.max_attachment_bytes,
+        shared_dir: config.shared_dir.clone(),
+        conflict_log: config
+            .log_conflicts
+            .then(|| config.conflict_log_path.clone()),
+        ..Default::default()
+    };
+    let watch_options = WatchOptions {
+        debounce: Duration::from_millis(debounce_ms),
+        poll_interval: Duration::from_secs(poll_secs.max(1)),
+    };
+
+    println!("Watching {} (Ctrl-C to stop)", stickies_path.display());
+    watch::watch(
+        &config.database_path,
+        &stickies_path,
+        &sync_options,
+        &watch_options,
+        |result| match result {
+            // Quiet syncs are the common case; only report ones that did something
+            Ok(report)
+                if report.changed() > 0
+                    || !report.warnings.is_empty()
+                    || !report.failures.is_empty()
+                    || !report.conflicts.is_empty() =>
+            {
+                super::sync::print_report(&report, false, verbose)
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Sync failed: {}", e),
+        },
+    )
+}
@@ -4,14 +4,36 @@
 use crate::{Result, StickyError};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Attachments larger than this are not copied into the database by default
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Config {
     pub database_path: PathBuf,
     pub log_conflicts: bool,
     pub conflict_log_path: PathBuf,
+    /// Largest attachment sync copies into the database; bigger ones stay on
+    /// the machine that has them. 0 disables the limit.
+    #[serde(default = "default_max_attachment_bytes")]
+    pub max_attachment_bytes: u64,
+    /// Shared mode: a synced folder (iCloud Drive, Dropbox) where each machine
+    /// appends its changes to its own log. `database_path` should then be a
+    /// local, unsynced path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_dir: Option<PathBuf>,
+    /// Identifies this installation in version vectors and per-machine sync
+    /// state. Generated by the first command that writes; unlike the hostname
+    /// it never changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub machine_id: Option<String>,
+}
+
+fn default_max_attachment_bytes() -> u64 {
+    DEFAULT_MAX_ATTACHMENT_BYTES
 }
 
 impl Default for Config {
@@ -25,6 +47,9 @@ impl Default for Config {
             database_path: data_dir.join("stickies.db"),
             log_conflicts: true,
             conflict_log_path: data_dir.join("conflicts.log"),
+            max_attachment_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
+            shared_dir: None,
+            machine_id: None,
         }
     }
 }
@@ -57,6 +82,56 @@ impl Config {
         Ok(())
     }
 
+    /// This installation's machine ID, generating one and saving it to the
+    /// config file at `config_path` the first time it's needed. The file is
+    /// re-read under an exclusive lock, so commands starting together agree
+    /// on one ID instead of each writing their own.
+    pub fn ensure_machine_id(&mut self, config_path: &Path) -> Result<String> {
+        if let Some(id) = &self.machine_id {
+            return Ok(id.clone());
+        }
+
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::options()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(config_path)?;
+        file.lock()?;
+
+        // Read by path: whoever held the lock before us may have replaced the
+        // file we locked with one that has an ID
+        let contents = fs::read_to_string(config_path)?;
+        let id = if contents.trim().is_empty() {
+            let id = uuid::Uuid::new_v4().to_string();
+            let mut config = self.clone();
+            config.machine_id = Some(id.clone());
+            let toml_str = toml::to_string_pretty(&config)
+                .map_err(|e| StickyError::Config(format!("Failed to serialize config: {}", e)))?;
+            replace_file(config_path, &toml_str)?;
+            id
+        } else {
+            let mut doc = contents.parse::<toml_edit::DocumentMut>().map_err(|e| {
+                StickyError::Config(format!("Invalid config {}: {}", config_path.display(), e))
+            })?;
+            match doc.get("machine_id").and_then(|item| item.as_str()) {
+                Some(id) => id.to_string(),
+                None => {
+                    // Edited in place so the user's comments and formatting are kept
+                    let id = uuid::Uuid::new_v4().to_string();
+                    doc.insert("machine_id", toml_edit::value(id.as_str()));
+                    replace_file(config_path, &doc.to_string())?;
+                    id
+                }
+            }
+        };
+
+        self.machine_id = Some(id.clone());
+        Ok(id)
+    }
+
     pub fn config_path() -> Result<PathBuf> {
         let proj_dirs = ProjectDirs::from("", "", "sticky-situation")
             .ok_or_else(|| StickyError::Config("Could not determine config dir".into()))?;
@@ -84,3 +159,19 @@ impl Config {
         Ok(config_path)
     }
 }
+
+/// Write `contents` to a sibling file and rename it over `path`, so readers
+/// never see a partial config
+fn replace_file(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name));
+
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(contents.as_bytes())?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -1,10 +1,15 @@
 // ABOUTME: SQLite database operations for stickies storage and search
 // ABOUTME: Handles schema creation, CRUD operations, and FTS5 full-text search
 
-use crate::Result;
-use rusqlite::{params, Connection, OptionalExtension};
-use std::cell::RefCell;
-use std::path::Path;
+use crate::filesystem::{rtfd::Attachment, Frame, StickyMetadata};
+use crate::query::{escape_fts, CompiledQuery, SearchQuery};
+use crate::version_vector::VersionVector;
+use crate::{tags, Result, StickyError};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 #[derive(Debug, Clone)]
 pub struct Sticky {
@@ -12,14 +17,144 @@ pub struct Sticky {
     pub content_text: String,
     pub rtf_data: Vec<u8>,
     pub plist_metadata: Vec<u8>,
+    pub is_floating: bool,
     pub color: String,
     pub modified_at: i64,
     pub created_at: i64,
     pub source_machine: String,
+    /// Modification time in nanoseconds since the epoch; 0 for rows written
+    /// before sub-second times were stored
+    pub modified_ns: i64,
+    /// SHA-256 of the RTF and attachments (see `RtfdBundle::content_hash`);
+    /// empty for rows written before hashes were stored
+    pub content_hash: String,
+    /// Edits to the content per machine ID; empty for rows written before
+    /// vectors were stored
+    pub version_vector: VersionVector,
+}
+
+/// Tag applied because the sticky's text contains `#tag`
+const TAG_SOURCE_HASHTAG: &str = "hashtag";
+/// Tag applied with `sticky tag`
+const TAG_SOURCE_MANUAL: &str = "manual";
+
+/// Markers placed around matching words in search snippets and highlights
+pub const HIGHLIGHT_START: &str = "[";
+pub const HIGHLIGHT_END: &str = "]";
+
+/// Approximate number of tokens in a search snippet
+const SNIPPET_TOKENS: i64 = 12;
+
+/// Characters of content shown for filter-only searches with no text to highlight
+const PREVIEW_CHARS: i64 = 80;
+
+/// A full-text search match with its BM25 score and highlighted text
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub sticky: Sticky,
+    /// BM25 score; lower is a better match
+    pub rank: f64,
+    /// Short excerpt around the matching words
+    pub snippet: String,
+    /// The whole content with every match marked, for full-content display
+    pub highlighted: String,
+}
+
+/// One sticky's window state within a saved layout
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutEntry {
+    pub uuid: String,
+    pub frame: Frame,
+    pub is_floating: bool,
+    pub is_collapsed: bool,
+}
+
+/// What is known about an attachment without loading its content
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachmentInfo {
+    pub filename: String,
+    pub mime_type: String,
+    pub size: u64,
+    /// False when the attachment was over the size limit and only recorded,
+    /// its content left on the machine that has it
+    pub stored: bool,
+}
+
+/// A sticky with its attachments, as written by `Database::insert_batch`
+#[derive(Debug, Clone)]
+pub struct StickyRecord {
+    pub sticky: Sticky,
+    pub attachments: Vec<Attachment>,
+    /// Attachments over the size limit, recorded without their content
+    pub skipped: Vec<AttachmentInfo>,
+}
+
+/// The columns sync needs to compare a sticky, without its RTF or attachments
+#[derive(Debug, Clone)]
+pub struct StoredVersion {
+    pub modified_at: i64,
+    pub modified_ns: i64,
+    pub content_hash: String,
+    pub source_machine: String,
+    pub plist_metadata: Vec<u8>,
+    pub version_vector: VersionVector,
+}
+
+/// A bundle's hash as of the last time this machine read it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanEntry {
+    pub modified_ns: i64,
+    pub content_hash: String,
+}
+
+/// The content a machine's Stickies directory had when it last synced a
+/// sticky, which both sides of the next sync are compared against
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncedVersion {
+    pub content_hash: String,
+    pub version_vector: VersionVector,
+}
+
+/// A sticky that failed to sync on a machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuarantineEntry {
+    pub uuid: String,
+    pub error: String,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+/// Who is running a sync against the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncLease {
+    /// Machine ID of the sync's installation; identifies the holder
+    pub machine: String,
+    /// Shown to the user when the lease blocks a sync
+    pub hostname: String,
+    pub pid: u32,
+    pub acquired_at: i64,
+}
+
+/// The version of a sticky last written to (or read from) the shared change logs
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishedVersion {
+    pub content_hash: String,
+    /// SHA-256 of the stored plist metadata
+    pub metadata_hash: String,
+    /// SHA-256 of the sticky's manual tags; empty when it has none
+    pub tags_hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutSummary {
+    pub name: String,
+    pub sticky_count: usize,
+    pub saved_at: i64,
 }
 
 pub struct Database {
-    conn: RefCell<Connection>,
+    conn: Mutex<Connection>,
+    path: PathBuf,
 }
 
 impl Database {
@@ -32,10 +167,14 @@ impl Database {
                 content_text TEXT,
                 rtf_data BLOB,
                 plist_metadata BLOB,
+                is_floating INTEGER NOT NULL DEFAULT 0,
                 color TEXT,
                 modified_at INTEGER,
                 created_at INTEGER,
-                source_machine TEXT
+                source_machine TEXT,
+                modified_ns INTEGER NOT NULL DEFAULT 0,
+                content_hash TEXT NOT NULL DEFAULT '',
+                version_vector TEXT NOT NULL DEFAULT ''
             )",
             [],
         )?;
@@ -46,6 +185,8 @@ impl Database {
                 sticky_uuid TEXT,
                 filename TEXT,
                 content BLOB,
+                mime_type TEXT,
+                size INTEGER,
                 FOREIGN KEY (sticky_uuid) REFERENCES stickies(uuid)
             )",
             [],
@@ -60,79 +201,953 @@ impl Database {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE
+            )",
+            [],
+        )?;
+
+        // A tag can be applied both by a #hashtag in the text and manually;
+        // each source is tracked separately so syncs only refresh hashtags
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sticky_tags (
+                sticky_uuid TEXT NOT NULL,
+                tag_id INTEGER NOT NULL,
+                source TEXT NOT NULL,
+                PRIMARY KEY (sticky_uuid, tag_id, source),
+                FOREIGN KEY (sticky_uuid) REFERENCES stickies(uuid),
+                FOREIGN KEY (tag_id) REFERENCES tags(id)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS layouts (
+                name TEXT NOT NULL,
+                sticky_uuid TEXT NOT NULL,
+                frame TEXT NOT NULL,
+                is_floating INTEGER NOT NULL,
+                is_collapsed INTEGER NOT NULL,
+                saved_at INTEGER NOT NULL,
+                PRIMARY KEY (name, sticky_uuid)
+            )",
+            [],
+        )?;
+
+        // Filesystem scan state, per machine since the database is shared
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS scan_cache (
+                machine TEXT NOT NULL,
+                sticky_uuid TEXT NOT NULL,
+                modified_ns INTEGER NOT NULL,
+                content_hash TEXT NOT NULL,
+                PRIMARY KEY (machine, sticky_uuid)
+            )",
+            [],
+        )?;
+
+        // What each machine's Stickies directory held after its last sync
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS synced_versions (
+                machine TEXT NOT NULL,
+                sticky_uuid TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                version_vector TEXT NOT NULL,
+                PRIMARY KEY (machine, sticky_uuid)
+            )",
+            [],
+        )?;
+
+        // The plist entry each machine's Stickies state file held after its
+        // last sync, so a window change can be told apart from a stale copy
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS synced_layouts (
+                machine TEXT NOT NULL,
+                sticky_uuid TEXT NOT NULL,
+                plist_metadata BLOB NOT NULL,
+                PRIMARY KEY (machine, sticky_uuid)
+            )",
+            [],
+        )?;
+
+        // Stickies whose bundles a committed sync still has to write on a
+        // machine; cleared once they are on disk
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_journal (
+                machine TEXT NOT NULL,
+                sticky_uuid TEXT NOT NULL,
+                PRIMARY KEY (machine, sticky_uuid)
+            )",
+            [],
+        )?;
+
+        // Stickies a sync on a machine couldn't read or write, kept for
+        // inspection; entries clear once the sticky syncs again
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS quarantine (
+                machine TEXT NOT NULL,
+                sticky_uuid TEXT NOT NULL,
+                error TEXT NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                PRIMARY KEY (machine, sticky_uuid)
+            )",
+            [],
+        )?;
+
+        // The sync currently running against this database, if any
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_lease (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                machine TEXT NOT NULL DEFAULT '',
+                hostname TEXT NOT NULL,
+                pid INTEGER NOT NULL,
+                acquired_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Shared mode: how far each machine's change log has been merged, and
+        // the version of each sticky this machine last published
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shared_offsets (
+                log TEXT PRIMARY KEY,
+                offset INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS shared_published (
+                sticky_uuid TEXT PRIMARY KEY,
+                content_hash TEXT NOT NULL,
+                metadata_hash TEXT NOT NULL,
+                tags_hash TEXT NOT NULL DEFAULT ''
+            )",
+            [],
+        )?;
+
+        // Columns added after the initial schema
+        ensure_column(
+            &conn,
+            "stickies",
+            "is_floating",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(&conn, "attachments", "mime_type", "TEXT")?;
+        ensure_column(&conn, "attachments", "size", "INTEGER")?;
+        ensure_column(
+            &conn,
+            "stickies",
+            "modified_ns",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            &conn,
+            "stickies",
+            "content_hash",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+        ensure_column(
+            &conn,
+            "stickies",
+            "version_vector",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+        ensure_column(&conn, "sync_lease", "machine", "TEXT NOT NULL DEFAULT ''")?;
+        ensure_column(
+            &conn,
+            "shared_published",
+            "tags_hash",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+
         Ok(Self {
-            conn: RefCell::new(conn),
+            conn: Mutex::new(conn),
+            path: path.to_path_buf(),
         })
     }
 
-    pub fn connection(&self) -> &RefCell<Connection> {
+    /// The file the database was opened from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn connection(&self) -> &Mutex<Connection> {
         &self.conn
     }
 
+    /// Lock the connection, recovering it if another thread panicked while
+    /// holding it (SQLite rolls back any transaction that was left open)
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `f` in one transaction, committing if it succeeds and rolling back
+    /// if it fails. Methods called inside it join the transaction.
+    pub fn in_transaction<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.lock().execute_batch("BEGIN IMMEDIATE")?;
+        match f() {
+            Ok(value) => {
+                self.lock().execute_batch("COMMIT")?;
+                Ok(value)
+            }
+            Err(e) => {
+                let _ = self.lock().execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    /// Run `f` in a transaction that is always rolled back, to see what its
+    /// writes would do without keeping them
+    pub fn rolled_back<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.lock().execute_batch("BEGIN")?;
+        let result = f();
+        self.lock().execute_batch("ROLLBACK")?;
+        result
+    }
+
     pub fn insert_sticky(&self, sticky: &Sticky) -> Result<()> {
-        let mut conn = self.conn.borrow_mut();
-        let tx = conn.transaction()?;
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        insert_sticky(&tx, sticky)?;
+        tx.commit()?;
 
-        // Delete from FTS index first to avoid corruption
-        tx.execute(
-            "DELETE FROM stickies_fts WHERE uuid = ?1",
-            params![&sticky.uuid],
-        )?;
+        Ok(())
+    }
 
-        tx.execute(
-            "INSERT OR REPLACE INTO stickies
-             (uuid, content_text, rtf_data, plist_metadata, color, modified_at, created_at, source_machine)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    /// Insert or replace many stickies and their attachments in a single
+    /// transaction
+    pub fn insert_batch(&self, records: &[StickyRecord]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        for record in records {
+            insert_sticky(&tx, &record.sticky)?;
+            save_attachments(
+                &tx,
+                &record.sticky.uuid,
+                &record.attachments,
+                &record.skipped,
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Update only the stored window metadata for a sticky, leaving its
+    /// content and modification time alone
+    pub fn update_metadata(&self, uuid: &str, metadata: &StickyMetadata) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE stickies SET plist_metadata = ?1, is_floating = ?2, color = ?3 WHERE uuid = ?4",
             params![
-                &sticky.uuid,
-                &sticky.content_text,
-                &sticky.rtf_data,
-                &sticky.plist_metadata,
-                &sticky.color,
-                sticky.modified_at,
-                sticky.created_at,
-                &sticky.source_machine,
+                metadata.to_bytes()?,
+                metadata.is_floating,
+                metadata.color_name(),
+                uuid
             ],
         )?;
+        Ok(())
+    }
+
+    /// Move a sticky and everything attached to it (attachments, tags, layout
+    /// entries, search index) from one UUID to another
+    pub fn rekey_sticky(&self, from: &str, to: &str) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        // Child rows still point at the old UUID until they are updated below
+        tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
 
-        // Insert into FTS index
         tx.execute(
-            "INSERT INTO stickies_fts (uuid, content_text) VALUES (?1, ?2)",
-            params![&sticky.uuid, &sticky.content_text],
+            "UPDATE stickies SET uuid = ?2 WHERE uuid = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE stickies_fts SET uuid = ?2 WHERE uuid = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE attachments SET sticky_uuid = ?2 WHERE sticky_uuid = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE sticky_tags SET sticky_uuid = ?2 WHERE sticky_uuid = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE layouts SET sticky_uuid = ?2 WHERE sticky_uuid = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE OR REPLACE synced_versions SET sticky_uuid = ?2 WHERE sticky_uuid = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE OR REPLACE synced_layouts SET sticky_uuid = ?2 WHERE sticky_uuid = ?1",
+            params![from, to],
         )?;
 
         tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace the stored attachments for a sticky with the given set
+    pub fn save_attachments(&self, uuid: &str, attachments: &[Attachment]) -> Result<()> {
+        self.save_attachments_with_skipped(uuid, attachments, &[])
+    }
 
+    /// Replace the stored attachments for a sticky, recording `skipped` ones
+    /// (over the size limit) by name, type and size only
+    pub fn save_attachments_with_skipped(
+        &self,
+        uuid: &str,
+        attachments: &[Attachment],
+        skipped: &[AttachmentInfo],
+    ) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        save_attachments(&tx, uuid, attachments, skipped)?;
+        tx.commit()?;
         Ok(())
     }
 
+    /// Attachments whose content is stored in the database
+    pub fn get_attachments(&self, uuid: &str) -> Result<Vec<Attachment>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT filename, content FROM attachments
+             WHERE sticky_uuid = ?1 AND content IS NOT NULL ORDER BY id",
+        )?;
+        let attachments = stmt
+            .query_map([uuid], |row| {
+                Ok(Attachment {
+                    filename: row.get(0)?,
+                    content: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(attachments)
+    }
+
+    /// Every attachment recorded for a sticky, including skipped ones. Rows
+    /// stored before types were recorded are sniffed on the fly.
+    pub fn get_attachment_info(&self, uuid: &str) -> Result<Vec<AttachmentInfo>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT filename, mime_type, size, content FROM attachments
+             WHERE sticky_uuid = ?1 ORDER BY id",
+        )?;
+        let info = stmt
+            .query_map([uuid], |row| {
+                let filename: String = row.get(0)?;
+                let mime_type: Option<String> = row.get(1)?;
+                let size: Option<i64> = row.get(2)?;
+                let content: Option<Vec<u8>> = row.get(3)?;
+                Ok(AttachmentInfo {
+                    mime_type: mime_type.unwrap_or_else(|| {
+                        let content = content.as_deref().unwrap_or_default();
+                        crate::mime::detect_with_name(content, &filename).to_string()
+                    }),
+                    filename,
+                    size: size
+                        .map(|s| s as u64)
+                        .unwrap_or_else(|| content.as_ref().map_or(0, |c| c.len() as u64)),
+                    stored: content.is_some(),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(info)
+    }
+
+    pub fn save_layout(&self, name: &str, entries: &[LayoutEntry], saved_at: i64) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+
+        tx.execute("DELETE FROM layouts WHERE name = ?1", [name])?;
+        for entry in entries {
+            tx.execute(
+                "INSERT INTO layouts (name, sticky_uuid, frame, is_floating, is_collapsed, saved_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    name,
+                    &entry.uuid,
+                    entry.frame.to_string(),
+                    entry.is_floating,
+                    entry.is_collapsed,
+                    saved_at,
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Entries of a saved layout; empty if no layout has that name
+    pub fn get_layout(&self, name: &str) -> Result<Vec<LayoutEntry>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT sticky_uuid, frame, is_floating, is_collapsed
+             FROM layouts WHERE name = ?1 ORDER BY sticky_uuid",
+        )?;
+        let entries = stmt
+            .query_map([name], |row| {
+                Ok(LayoutEntry {
+                    uuid: row.get(0)?,
+                    frame: row.get::<_, String>(1)?.parse().unwrap_or_default(),
+                    is_floating: row.get(2)?,
+                    is_collapsed: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    pub fn list_layouts(&self) -> Result<Vec<LayoutSummary>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT name, COUNT(*), MAX(saved_at) FROM layouts GROUP BY name ORDER BY name",
+        )?;
+        let layouts = stmt
+            .query_map([], |row| {
+                Ok(LayoutSummary {
+                    name: row.get(0)?,
+                    sticky_count: row.get::<_, i64>(1)? as usize,
+                    saved_at: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(layouts)
+    }
+
+    /// Delete a saved layout, returning false if it did not exist
+    pub fn delete_layout(&self, name: &str) -> Result<bool> {
+        let conn = self.lock();
+        let deleted = conn.execute("DELETE FROM layouts WHERE name = ?1", [name])?;
+        Ok(deleted > 0)
+    }
+
+    /// Manually tag a sticky. Manual tags have no RTF representation and are
+    /// kept across syncs.
+    pub fn add_tag(&self, uuid: &str, tag: &str) -> Result<()> {
+        let conn = self.lock();
+        insert_sticky_tag(&conn, uuid, tag, TAG_SOURCE_MANUAL)
+    }
+
+    /// Remove a manual tag. Returns true if the sticky still carries the tag
+    /// because of a #hashtag in its text.
+    pub fn remove_tag(&self, uuid: &str, tag: &str) -> Result<bool> {
+        let conn = self.lock();
+        conn.execute(
+            "DELETE FROM sticky_tags
+             WHERE sticky_uuid = ?1 AND source = ?2
+               AND tag_id = (SELECT id FROM tags WHERE name = ?3)",
+            params![uuid, TAG_SOURCE_MANUAL, tag],
+        )?;
+
+        let from_hashtag: bool = conn.query_row(
+            "SELECT EXISTS (
+                SELECT 1 FROM sticky_tags st JOIN tags t ON t.id = st.tag_id
+                WHERE st.sticky_uuid = ?1 AND st.source = ?2 AND t.name = ?3
+            )",
+            params![uuid, TAG_SOURCE_HASHTAG, tag],
+            |row| row.get(0),
+        )?;
+
+        Ok(from_hashtag)
+    }
+
+    /// Tags added with `add_tag`, sorted by name
+    /// Manual tags of every sticky that has any, each list sorted by name
+    pub fn get_all_manual_tags(&self) -> Result<HashMap<String, Vec<String>>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT st.sticky_uuid, t.name FROM sticky_tags st
+             JOIN tags t ON t.id = st.tag_id
+             WHERE st.source = ?1
+             ORDER BY st.sticky_uuid, t.name",
+        )?;
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        let rows = stmt.query_map([TAG_SOURCE_MANUAL], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (uuid, tag) = row?;
+            tags.entry(uuid).or_default().push(tag);
+        }
+        Ok(tags)
+    }
+
+    pub fn get_manual_tags(&self, uuid: &str) -> Result<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT t.name FROM sticky_tags st
+             JOIN tags t ON t.id = st.tag_id
+             WHERE st.sticky_uuid = ?1 AND st.source = ?2
+             ORDER BY t.name",
+        )?;
+        let tags = stmt
+            .query_map(params![uuid, TAG_SOURCE_MANUAL], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(tags)
+    }
+
+    /// All tags on a sticky, from hashtags and manual tagging, sorted by name
+    pub fn get_tags(&self, uuid: &str) -> Result<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT t.name FROM sticky_tags st
+             JOIN tags t ON t.id = st.tag_id
+             WHERE st.sticky_uuid = ?1
+             ORDER BY t.name",
+        )?;
+        let tags = stmt
+            .query_map([uuid], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(tags)
+    }
+
     pub fn get_sticky(&self, uuid: &str) -> Result<Option<Sticky>> {
-        let conn = self.conn.borrow();
+        let conn = self.lock();
         let mut stmt = conn.prepare(
-            "SELECT uuid, content_text, rtf_data, plist_metadata, color, modified_at, created_at, source_machine
+            "SELECT uuid, content_text, rtf_data, plist_metadata, is_floating, color, modified_at, created_at, source_machine, modified_ns, content_hash, version_vector
              FROM stickies WHERE uuid = ?1"
         )?;
 
-        let sticky = stmt
-            .query_row([uuid], |row| {
-                Ok(Sticky {
+        let sticky = stmt.query_row([uuid], sticky_from_row).optional()?;
+
+        Ok(sticky)
+    }
+
+    /// Every sticky's modification time and hash in one query, for sync
+    pub fn get_modified_times(&self) -> Result<HashMap<String, StoredVersion>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT uuid, modified_at, modified_ns, content_hash, source_machine, plist_metadata, version_vector
+             FROM stickies",
+        )?;
+        let versions = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    StoredVersion {
+                        modified_at: row.get(1)?,
+                        modified_ns: row.get(2)?,
+                        content_hash: row.get(3)?,
+                        source_machine: row.get(4)?,
+                        plist_metadata: row.get(5)?,
+                        version_vector: row.get(6)?,
+                    },
+                ))
+            })?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(versions)
+    }
+
+    /// Record that a sticky's content includes the edits in `version_vector`
+    pub fn set_version_vector(&self, uuid: &str, version_vector: &VersionVector) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE stickies SET version_vector = ?1 WHERE uuid = ?2",
+            params![version_vector, uuid],
+        )?;
+        Ok(())
+    }
+
+    /// Store a hash for a row written before hashes existed
+    pub fn set_content_hash(&self, uuid: &str, content_hash: &str) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "UPDATE stickies SET content_hash = ?1 WHERE uuid = ?2",
+            params![content_hash, uuid],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_scan_cache(&self, machine: &str) -> Result<HashMap<String, ScanEntry>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT sticky_uuid, modified_ns, content_hash FROM scan_cache WHERE machine = ?1",
+        )?;
+        let entries = stmt
+            .query_map([machine], |row| {
+                Ok((
+                    row.get(0)?,
+                    ScanEntry {
+                        modified_ns: row.get(1)?,
+                        content_hash: row.get(2)?,
+                    },
+                ))
+            })?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Replace this machine's scan cache
+    pub fn save_scan_cache(
+        &self,
+        machine: &str,
+        entries: &HashMap<String, ScanEntry>,
+    ) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        tx.execute("DELETE FROM scan_cache WHERE machine = ?1", [machine])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO scan_cache (machine, sticky_uuid, modified_ns, content_hash)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (uuid, entry) in entries {
+                stmt.execute(params![
+                    machine,
+                    uuid,
+                    entry.modified_ns,
+                    &entry.content_hash
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move a machine's sync state, and the stickies it wrote, to a new name,
+    /// e.g. from its hostname to its machine ID. Rows already under the new
+    /// name are kept.
+    pub fn rename_machine(&self, from: &str, to: &str) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        for table in [
+            "scan_cache",
+            "sync_journal",
+            "quarantine",
+            "synced_versions",
+            "synced_layouts",
+        ] {
+            tx.execute(
+                &format!(
+                    "UPDATE OR IGNORE {} SET machine = ?2 WHERE machine = ?1",
+                    table
+                ),
+                params![from, to],
+            )?;
+            tx.execute(&format!("DELETE FROM {} WHERE machine = ?1", table), [from])?;
+        }
+        tx.execute(
+            "UPDATE stickies SET source_machine = ?2 WHERE source_machine = ?1",
+            params![from, to],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_synced_versions(&self, machine: &str) -> Result<HashMap<String, SyncedVersion>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT sticky_uuid, content_hash, version_vector FROM synced_versions WHERE machine = ?1",
+        )?;
+        let versions = stmt
+            .query_map([machine], |row| {
+                Ok((
+                    row.get(0)?,
+                    SyncedVersion {
+                        content_hash: row.get(1)?,
+                        version_vector: row.get(2)?,
+                    },
+                ))
+            })?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(versions)
+    }
+
+    /// Record what `machine`'s Stickies directory now holds for these stickies
+    pub fn save_synced_versions(
+        &self,
+        machine: &str,
+        versions: &[(String, SyncedVersion)],
+    ) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO synced_versions (machine, sticky_uuid, content_hash, version_vector)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (uuid, version) in versions {
+                stmt.execute(params![
+                    machine,
+                    uuid,
+                    &version.content_hash,
+                    &version.version_vector
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The plist metadata `machine`'s state file held after its last sync
+    pub fn get_synced_layouts(&self, machine: &str) -> Result<HashMap<String, Vec<u8>>> {
+        let conn = self.lock();
+        let mut stmt = conn
+            .prepare("SELECT sticky_uuid, plist_metadata FROM synced_layouts WHERE machine = ?1")?;
+        let layouts = stmt
+            .query_map([machine], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(layouts)
+    }
+
+    /// Record what `machine`'s state file now holds for these stickies
+    pub fn save_synced_layouts(&self, machine: &str, layouts: &[(String, Vec<u8>)]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO synced_layouts (machine, sticky_uuid, plist_metadata)
+                 VALUES (?1, ?2, ?3)",
+            )?;
+            for (uuid, plist_metadata) in layouts {
+                stmt.execute(params![machine, uuid, plist_metadata])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record stickies whose bundles still have to be written on `machine`
+    pub fn journal_exports(&self, machine: &str, uuids: &[String]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO sync_journal (machine, sticky_uuid) VALUES (?1, ?2)",
+            )?;
+            for uuid in uuids {
+                stmt.execute(params![machine, uuid])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn pending_exports(&self, machine: &str) -> Result<Vec<String>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT sticky_uuid FROM sync_journal WHERE machine = ?1 ORDER BY sticky_uuid",
+        )?;
+        let uuids = stmt
+            .query_map([machine], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(uuids)
+    }
+
+    /// Remove journal entries whose bundles have been written
+    pub fn clear_journal(&self, machine: &str, uuids: &[String]) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        for uuid in uuids {
+            tx.execute(
+                "DELETE FROM sync_journal WHERE machine = ?1 AND sticky_uuid = ?2",
+                params![machine, uuid],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace a machine's quarantine with the failures from its latest sync,
+    /// keeping when each sticky first failed
+    pub fn save_quarantine(
+        &self,
+        machine: &str,
+        failures: &[(String, String)],
+        now: i64,
+    ) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.savepoint()?;
+        let previous: Vec<String> = {
+            let mut stmt = tx.prepare("SELECT sticky_uuid FROM quarantine WHERE machine = ?1")?;
+            let uuids = stmt
+                .query_map([machine], |row| row.get(0))?
+                .collect::<std::result::Result<_, _>>()?;
+            uuids
+        };
+        for uuid in previous {
+            if !failures.iter().any(|(failed, _)| *failed == uuid) {
+                tx.execute(
+                    "DELETE FROM quarantine WHERE machine = ?1 AND sticky_uuid = ?2",
+                    params![machine, uuid],
+                )?;
+            }
+        }
+        for (uuid, error) in failures {
+            tx.execute(
+                "INSERT INTO quarantine (machine, sticky_uuid, error, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT (machine, sticky_uuid)
+                 DO UPDATE SET error = excluded.error, last_seen = excluded.last_seen",
+                params![machine, uuid, error, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_quarantine(&self, machine: &str) -> Result<Vec<QuarantineEntry>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT sticky_uuid, error, first_seen, last_seen FROM quarantine
+             WHERE machine = ?1 ORDER BY sticky_uuid",
+        )?;
+        let entries = stmt
+            .query_map([machine], |row| {
+                Ok(QuarantineEntry {
                     uuid: row.get(0)?,
-                    content_text: row.get(1)?,
-                    rtf_data: row.get(2)?,
-                    plist_metadata: row.get(3)?,
-                    color: row.get(4)?,
-                    modified_at: row.get(5)?,
-                    created_at: row.get(6)?,
-                    source_machine: row.get(7)?,
+                    error: row.get(1)?,
+                    first_seen: row.get(2)?,
+                    last_seen: row.get(3)?,
                 })
-            })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Forget a machine's quarantined stickies, returning how many there were
+    pub fn clear_quarantine(&self, machine: &str) -> Result<usize> {
+        let conn = self.lock();
+        let count = conn.execute("DELETE FROM quarantine WHERE machine = ?1", [machine])?;
+        Ok(count)
+    }
+
+    /// Take the sync lease unless someone else holds it. `is_stale` decides
+    /// whether an existing lease was abandoned and can be taken over.
+    pub fn acquire_lease(
+        &self,
+        lease: &SyncLease,
+        is_stale: impl FnOnce(&SyncLease) -> bool,
+    ) -> Result<()> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+        let current = tx
+            .query_row(
+                "SELECT machine, hostname, pid, acquired_at FROM sync_lease WHERE id = 1",
+                [],
+                |row| {
+                    Ok(SyncLease {
+                        machine: row.get(0)?,
+                        hostname: row.get(1)?,
+                        pid: row.get(2)?,
+                        acquired_at: row.get(3)?,
+                    })
+                },
+            )
             .optional()?;
+        if let Some(current) = current {
+            let ours = current.machine == lease.machine && current.pid == lease.pid;
+            if !ours && !is_stale(&current) {
+                return Err(StickyError::Locked {
+                    hostname: current.hostname,
+                    pid: current.pid,
+                });
+            }
+        }
 
-        Ok(sticky)
+        tx.execute(
+            "INSERT OR REPLACE INTO sync_lease (id, machine, hostname, pid, acquired_at)
+             VALUES (1, ?1, ?2, ?3, ?4)",
+            params![
+                &lease.machine,
+                &lease.hostname,
+                lease.pid,
+                lease.acquired_at
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Give up the sync lease if it is still ours
+    pub fn release_lease(&self, machine: &str, pid: u32) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "DELETE FROM sync_lease WHERE id = 1 AND machine = ?1 AND pid = ?2",
+            params![machine, pid],
+        )?;
+        Ok(())
+    }
+
+    /// Byte offset up to which a change log has been merged
+    pub fn get_log_offset(&self, log: &str) -> Result<u64> {
+        let conn = self.lock();
+        let offset: Option<i64> = conn
+            .query_row(
+                "SELECT offset FROM shared_offsets WHERE log = ?1",
+                [log],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(offset.unwrap_or(0) as u64)
+    }
+
+    pub fn set_log_offset(&self, log: &str, offset: u64) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO shared_offsets (log, offset) VALUES (?1, ?2)",
+            params![log, offset as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_published(&self) -> Result<HashMap<String, PublishedVersion>> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare(
+            "SELECT sticky_uuid, content_hash, metadata_hash, tags_hash FROM shared_published",
+        )?;
+        let published = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    PublishedVersion {
+                        content_hash: row.get(1)?,
+                        metadata_hash: row.get(2)?,
+                        tags_hash: row.get(3)?,
+                    },
+                ))
+            })?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(published)
+    }
+
+    pub fn set_published(&self, uuid: &str, version: &PublishedVersion) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "INSERT OR REPLACE INTO shared_published
+             (sticky_uuid, content_hash, metadata_hash, tags_hash)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                uuid,
+                &version.content_hash,
+                &version.metadata_hash,
+                &version.tags_hash
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_published(&self, uuid: &str) -> Result<()> {
+        let conn = self.lock();
+        conn.execute(
+            "DELETE FROM shared_published WHERE sticky_uuid = ?1",
+            [uuid],
+        )?;
+        Ok(())
     }
 
     pub fn get_all_uuids(&self) -> Result<Vec<String>> {
-        let conn = self.conn.borrow();
+        let conn = self.lock();
         let mut stmt = conn.prepare("SELECT uuid FROM stickies")?;
         let uuids = stmt
             .query_map([], |row| row.get(0))?
@@ -141,79 +1156,289 @@ impl Database {
     }
 
     pub fn get_all_stickies(&self) -> Result<Vec<Sticky>> {
-        let conn = self.conn.borrow();
+        let conn = self.lock();
         let mut stmt = conn.prepare(
-            "SELECT uuid, content_text, rtf_data, plist_metadata, color, modified_at, created_at, source_machine
+            "SELECT uuid, content_text, rtf_data, plist_metadata, is_floating, color, modified_at, created_at, source_machine, modified_ns, content_hash, version_vector
              FROM stickies"
         )?;
 
         let stickies = stmt
-            .query_map([], |row| {
-                Ok(Sticky {
-                    uuid: row.get(0)?,
-                    content_text: row.get(1)?,
-                    rtf_data: row.get(2)?,
-                    plist_metadata: row.get(3)?,
-                    color: row.get(4)?,
-                    modified_at: row.get(5)?,
-                    created_at: row.get(6)?,
-                    source_machine: row.get(7)?,
-                })
-            })?
+            .query_map([], sticky_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(stickies)
     }
 
     pub fn get_stickies_by_color(&self, color: &str) -> Result<Vec<Sticky>> {
-        let conn = self.conn.borrow();
+        let conn = self.lock();
         let mut stmt = conn.prepare(
-            "SELECT uuid, content_text, rtf_data, plist_metadata, color, modified_at, created_at, source_machine
+            "SELECT uuid, content_text, rtf_data, plist_metadata, is_floating, color, modified_at, created_at, source_machine, modified_ns, content_hash, version_vector
              FROM stickies WHERE color = ?1"
         )?;
 
         let stickies = stmt
-            .query_map([color], |row| {
-                Ok(Sticky {
-                    uuid: row.get(0)?,
-                    content_text: row.get(1)?,
-                    rtf_data: row.get(2)?,
-                    plist_metadata: row.get(3)?,
-                    color: row.get(4)?,
-                    modified_at: row.get(5)?,
-                    created_at: row.get(6)?,
-                    source_machine: row.get(7)?,
-                })
-            })?
+            .query_map([color], sticky_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         Ok(stickies)
     }
 
+    /// Search for stickies containing the given words, treated literally
     pub fn search(&self, query: &str) -> Result<Vec<Sticky>> {
-        let conn = self.conn.borrow();
-        let mut stmt = conn.prepare(
-            "SELECT s.uuid, s.content_text, s.rtf_data, s.plist_metadata, s.color, s.modified_at, s.created_at, s.source_machine
-             FROM stickies s
-             JOIN stickies_fts fts ON s.uuid = fts.uuid
-             WHERE stickies_fts MATCH ?1"
-        )?;
+        let hits = self.search_ranked(query, None, None, 0)?;
+        Ok(hits.into_iter().map(|hit| hit.sticky).collect())
+    }
 
-        let stickies = stmt
-            .query_map([query], |row| {
-                Ok(Sticky {
-                    uuid: row.get(0)?,
-                    content_text: row.get(1)?,
-                    rtf_data: row.get(2)?,
-                    plist_metadata: row.get(3)?,
-                    color: row.get(4)?,
-                    modified_at: row.get(5)?,
-                    created_at: row.get(6)?,
-                    source_machine: row.get(7)?,
+    /// Full-text search for the given words (treated literally) ordered by BM25
+    /// relevance (best match first), with an excerpt around the matching words
+    /// and the fully highlighted content for each hit
+    pub fn search_ranked(
+        &self,
+        query: &str,
+        color: Option<&str>,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let mut compiled = CompiledQuery {
+            fts_match: Some(escape_fts(query)),
+            ..Default::default()
+        };
+
+        if let Some(color) = color {
+            compiled.conditions.push("s.color = ?".to_string());
+            compiled.params.push(Value::Text(color.to_string()));
+        }
+
+        self.run_search(&compiled, limit, offset)
+    }
+
+    /// Search using the structured query language (text plus filters such as
+    /// `color:pink`). Text matches are ranked by BM25; filter-only queries are
+    /// ordered by most recently modified.
+    pub fn search_query(
+        &self,
+        query: &SearchQuery,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<SearchHit>> {
+        self.run_search(&query.compile(), limit, offset)
+    }
+
+    fn run_search(
+        &self,
+        compiled: &CompiledQuery,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let mut params: Vec<Value> = Vec::new();
+        let mut conditions = Vec::new();
+
+        let mut sql = String::from(
+            "SELECT s.uuid, s.content_text, s.rtf_data, s.plist_metadata, s.is_floating, s.color, s.modified_at, s.created_at, s.source_machine, s.modified_ns, s.content_hash, s.version_vector, ",
+        );
+
+        if let Some(fts_match) = &compiled.fts_match {
+            sql.push_str(
+                "bm25(stickies_fts), snippet(stickies_fts, 1, ?, ?, '...', ?),
+                 highlight(stickies_fts, 1, ?, ?)
+                 FROM stickies s
+                 JOIN stickies_fts ON s.uuid = stickies_fts.uuid",
+            );
+            params.push(Value::Text(HIGHLIGHT_START.to_string()));
+            params.push(Value::Text(HIGHLIGHT_END.to_string()));
+            params.push(Value::Integer(SNIPPET_TOKENS));
+            params.push(Value::Text(HIGHLIGHT_START.to_string()));
+            params.push(Value::Text(HIGHLIGHT_END.to_string()));
+
+            conditions.push("stickies_fts MATCH ?".to_string());
+            params.push(Value::Text(fts_match.clone()));
+        } else {
+            sql.push_str("0.0, substr(s.content_text, 1, ?), s.content_text FROM stickies s");
+            params.push(Value::Integer(PREVIEW_CHARS));
+        }
+
+        conditions.extend(compiled.conditions.iter().cloned());
+        params.extend(compiled.params.iter().cloned());
+
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        if compiled.fts_match.is_some() {
+            sql.push_str(" ORDER BY bm25(stickies_fts)");
+        } else {
+            sql.push_str(" ORDER BY s.modified_at DESC");
+        }
+
+        // SQLite treats a negative LIMIT as "no limit"
+        sql.push_str(" LIMIT ? OFFSET ?");
+        params.push(Value::Integer(limit.map(|l| l as i64).unwrap_or(-1)));
+        params.push(Value::Integer(offset as i64));
+
+        let conn = self.lock();
+        let mut stmt = conn.prepare(&sql)?;
+
+        // FTS5 only parses the MATCH expression once the query runs
+        let hits = stmt
+            .query_map(params_from_iter(params), |row| {
+                Ok(SearchHit {
+                    sticky: sticky_from_row(row)?,
+                    rank: row.get(12)?,
+                    snippet: row.get(13)?,
+                    highlighted: row.get(14)?,
                 })
-            })?
-            .collect::<std::result::Result<Vec<_>, _>>()?;
+            })
+            .and_then(|rows| rows.collect::<std::result::Result<Vec<_>, _>>())
+            .map_err(|err| fts_error(err, compiled.raw))?;
 
-        Ok(stickies)
+        Ok(hits)
+    }
+}
+
+fn insert_sticky_tag(conn: &Connection, uuid: &str, tag: &str, source: &str) -> Result<()> {
+    conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])?;
+    conn.execute(
+        "INSERT OR IGNORE INTO sticky_tags (sticky_uuid, tag_id, source)
+         SELECT ?1, id, ?2 FROM tags WHERE name = ?3",
+        params![uuid, source, tag],
+    )?;
+    Ok(())
+}
+
+fn sticky_from_row(row: &Row) -> rusqlite::Result<Sticky> {
+    Ok(Sticky {
+        uuid: row.get(0)?,
+        content_text: row.get(1)?,
+        rtf_data: row.get(2)?,
+        plist_metadata: row.get(3)?,
+        is_floating: row.get(4)?,
+        color: row.get(5)?,
+        modified_at: row.get(6)?,
+        created_at: row.get(7)?,
+        source_machine: row.get(8)?,
+        modified_ns: row.get(9)?,
+        content_hash: row.get(10)?,
+        version_vector: row.get(11)?,
+    })
+}
+
+/// Turn FTS5 expression parse failures into a friendly InvalidQuery error.
+/// FTS5 reports a bare `word:` as "no such column", but only raw queries can
+/// contain one; otherwise that message means a real SQL bug.
+fn fts_error(err: rusqlite::Error, raw: bool) -> StickyError {
+    if let rusqlite::Error::SqliteFailure(_, Some(message)) = &err {
+        let is_fts_syntax = message.starts_with("fts5:")
+            || message.starts_with("unterminated string")
+            || (raw && message.starts_with("no such column"));
+
+        if is_fts_syntax {
+            return StickyError::InvalidQuery {
+                message: message.clone(),
+                hint: "Wrap special characters in double quotes, or search without --fts to match text literally".into(),
+            };
+        }
     }
+
+    StickyError::Database(err)
+}
+
+/// Write a sticky row, its search index entry and hashtags within `tx`
+fn insert_sticky(tx: &Connection, sticky: &Sticky) -> Result<()> {
+    // Delete from FTS index first to avoid corruption
+    tx.execute(
+        "DELETE FROM stickies_fts WHERE uuid = ?1",
+        params![&sticky.uuid],
+    )?;
+
+    tx.execute(
+        "INSERT OR REPLACE INTO stickies
+         (uuid, content_text, rtf_data, plist_metadata, is_floating, color, modified_at, created_at, source_machine, modified_ns, content_hash, version_vector)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            &sticky.uuid,
+            &sticky.content_text,
+            &sticky.rtf_data,
+            &sticky.plist_metadata,
+            sticky.is_floating,
+            &sticky.color,
+            sticky.modified_at,
+            sticky.created_at,
+            &sticky.source_machine,
+            sticky.modified_ns,
+            &sticky.content_hash,
+            &sticky.version_vector,
+        ],
+    )?;
+
+    // Insert into FTS index
+    tx.execute(
+        "INSERT INTO stickies_fts (uuid, content_text) VALUES (?1, ?2)",
+        params![&sticky.uuid, &sticky.content_text],
+    )?;
+
+    // Refresh hashtag-derived tags; manual tags are left untouched
+    tx.execute(
+        "DELETE FROM sticky_tags WHERE sticky_uuid = ?1 AND source = ?2",
+        params![&sticky.uuid, TAG_SOURCE_HASHTAG],
+    )?;
+    for tag in tags::extract_hashtags(&sticky.content_text) {
+        insert_sticky_tag(tx, &sticky.uuid, &tag, TAG_SOURCE_HASHTAG)?;
+    }
+
+    Ok(())
+}
+
+/// Replace a sticky's attachment rows within `tx`
+fn save_attachments(
+    tx: &Connection,
+    uuid: &str,
+    attachments: &[Attachment],
+    skipped: &[AttachmentInfo],
+) -> Result<()> {
+    tx.execute("DELETE FROM attachments WHERE sticky_uuid = ?1", [uuid])?;
+    for attachment in attachments {
+        tx.execute(
+            "INSERT INTO attachments (sticky_uuid, filename, content, mime_type, size)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                uuid,
+                &attachment.filename,
+                &attachment.content,
+                attachment.mime_type(),
+                attachment.size() as i64
+            ],
+        )?;
+    }
+    for attachment in skipped {
+        tx.execute(
+            "INSERT INTO attachments (sticky_uuid, filename, content, mime_type, size)
+             VALUES (?1, ?2, NULL, ?3, ?4)",
+            params![
+                uuid,
+                &attachment.filename,
+                &attachment.mime_type,
+                attachment.size as i64
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Add a column to an existing table when opening a database created by an
+/// older version that lacks it
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if !columns.iter().any(|c| c == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
 }
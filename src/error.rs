@@ -1,5 +1,5 @@
 // ABOUTME: Error types for sticky-situation using thiserror
-// ABOUTME: Provides StickyError enum covering filesystem, database, and sync errors
+// ABOUTME: Provides StickyError enum covering filesystem, database, sync and input errors
 
 use thiserror::Error;
 
@@ -25,4 +25,16 @@ pub enum StickyError {
 
     #[error("Not found: {0}")]
     NotFound(String),
+
+    #[error("Another sync is running on {hostname} (pid {pid}); try again when it finishes")]
+    Locked { hostname: String, pid: u32 },
+
+    #[error("{0} sticky/stickies failed to sync; see `sticky quarantine`")]
+    SyncFailed(usize),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Invalid search query: {message}\nHint: {hint}")]
+    InvalidQuery { message: String, hint: String },
 }
@@ -0,0 +1,39 @@
+// ABOUTME: Quits and reopens Stickies.app around edits to its files
+// ABOUTME: Stickies saves its window state on quit, overwriting edits made while it ran
+
+use crate::Result;
+use std::process::Command;
+
+/// Run `edit` with Stickies.app quit. Stickies writes its state file when it
+/// quits, so anything changed in it while Stickies runs would be lost. If it
+/// was running (or `launch` is set) it is opened again afterwards, even when
+/// `edit` fails.
+pub fn while_quit<T>(launch: bool, edit: impl FnOnce() -> Result<T>) -> Result<T> {
+    let was_running = quit()?;
+    let result = edit();
+    if was_running || launch {
+        let opened = open();
+        return result.and_then(|value| opened.map(|()| value));
+    }
+    result
+}
+
+/// Quit Stickies.app if it is running, returning whether it was
+fn quit() -> Result<bool> {
+    // Without pgrep (not macOS) there is no Stickies.app to quit
+    let running = Command::new("pgrep")
+        .arg("Stickies")
+        .output()
+        .is_ok_and(|output| output.status.success());
+
+    if running {
+        Command::new("killall").arg("Stickies").status()?;
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+    Ok(running)
+}
+
+fn open() -> Result<()> {
+    Command::new("open").arg("-a").arg("Stickies").status()?;
+    Ok(())
+}
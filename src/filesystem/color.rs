@@ -0,0 +1,112 @@
+// ABOUTME: Sticky colors as stored in the Stickies plist "Color" index
+// ABOUTME: Converts between plist indices, names used in the database and CLI, and RGB hex
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The color of a sticky. Indices Stickies.app may add in future releases
+/// are kept as `Unknown` so they survive a round trip through the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StickyColor {
+    #[default]
+    Yellow,
+    Blue,
+    Green,
+    Pink,
+    Purple,
+    Gray,
+    Unknown(i64),
+}
+
+impl StickyColor {
+    /// Every color Stickies.app offers, in menu order
+    pub const ALL: [StickyColor; 6] = [
+        StickyColor::Yellow,
+        StickyColor::Blue,
+        StickyColor::Green,
+        StickyColor::Pink,
+        StickyColor::Purple,
+        StickyColor::Gray,
+    ];
+
+    pub fn from_index(index: i64) -> Self {
+        match index {
+            0 => Self::Yellow,
+            1 => Self::Blue,
+            2 => Self::Green,
+            3 => Self::Pink,
+            4 => Self::Purple,
+            5 => Self::Gray,
+            other => Self::Unknown(other),
+        }
+    }
+
+    /// The value written to the plist "Color" key
+    pub fn index(self) -> i64 {
+        match self {
+            Self::Yellow => 0,
+            Self::Blue => 1,
+            Self::Green => 2,
+            Self::Pink => 3,
+            Self::Purple => 4,
+            Self::Gray => 5,
+            Self::Unknown(index) => index,
+        }
+    }
+
+    /// Background color as `#RRGGBB`, or `None` for indices we don't know
+    pub fn hex(self) -> Option<&'static str> {
+        match self {
+            Self::Yellow => Some("#FEF49C"),
+            Self::Blue => Some("#ADF4FF"),
+            Self::Green => Some("#B2FFA1"),
+            Self::Pink => Some("#FFC7C7"),
+            Self::Purple => Some("#B6CAFF"),
+            Self::Gray => Some("#EEEEEE"),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for StickyColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Yellow => f.write_str("yellow"),
+            Self::Blue => f.write_str("blue"),
+            Self::Green => f.write_str("green"),
+            Self::Pink => f.write_str("pink"),
+            Self::Purple => f.write_str("purple"),
+            Self::Gray => f.write_str("gray"),
+            Self::Unknown(index) => write!(f, "color-{}", index),
+        }
+    }
+}
+
+impl FromStr for StickyColor {
+    type Err = String;
+
+    /// Parse a color name (case-insensitive) or `color-N` for an unknown index
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        match name.as_str() {
+            "yellow" => Ok(Self::Yellow),
+            "blue" => Ok(Self::Blue),
+            "green" => Ok(Self::Green),
+            "pink" => Ok(Self::Pink),
+            "purple" => Ok(Self::Purple),
+            "gray" | "grey" => Ok(Self::Gray),
+            _ => name
+                .strip_prefix("color-")
+                .and_then(|index| index.parse().ok())
+                .map(Self::from_index)
+                .ok_or_else(|| {
+                    let names: Vec<String> = Self::ALL.iter().map(|c| c.to_string()).collect();
+                    format!(
+                        "Invalid color '{}': expected one of {}",
+                        s,
+                        names.join(", ")
+                    )
+                }),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// ABOUTME: Window geometry for sticky frames stored in the Stickies plist
+// ABOUTME: Parses and formats Cocoa rect strings like "{{100, 100}, {250, 250}}"
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A sticky's window rectangle in screen points (Cocoa coordinates, origin at
+/// the bottom-left of the main screen)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Frame {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn with_position(self, x: f64, y: f64) -> Self {
+        Self { x, y, ..self }
+    }
+
+    pub fn with_size(self, width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            ..self
+        }
+    }
+
+    /// Move (and if necessary shrink) the frame so it lies entirely within
+    /// `screen`
+    pub fn clamped_to(self, screen: &Frame) -> Self {
+        let width = self.width.min(screen.width);
+        let height = self.height.min(screen.height);
+        let x = self.x.clamp(screen.x, screen.x + screen.width - width);
+        let y = self.y.clamp(screen.y, screen.y + screen.height - height);
+        Self::new(x, y, width, height)
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new(100.0, 100.0, 250.0, 250.0)
+    }
+}
+
+impl FromStr for Frame {
+    type Err = String;
+
+    /// Parse `{{x, y}, {width, height}}` as written by NSStringFromRect
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers: Vec<f64> = s
+            .split(['{', '}', ','])
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("Invalid frame '{}'", s))?;
+
+        match numbers.as_slice() {
+            [x, y, width, height]
+                if s.trim_start().starts_with("{{") && numbers.iter().all(|n| n.is_finite()) =>
+            {
+                Ok(Self::new(*x, *y, *width, *height))
+            }
+            _ => Err(format!("Invalid frame '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{{{{}, {}}}, {{{}, {}}}}}",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+/// Parse a size given on the command line as `WIDTHxHEIGHT`, e.g. `300x200`
+pub fn parse_size(s: &str) -> Option<(f64, f64)> {
+    let (width, height) = s.split_once(['x', 'X'])?;
+    let (width, height) = (parse_coordinate(width)?, parse_coordinate(height)?);
+    (width > 0.0 && height > 0.0).then_some((width, height))
+}
+
+/// Parse a position given on the command line as `X,Y`, e.g. `40,40`
+pub fn parse_position(s: &str) -> Option<(f64, f64)> {
+    let (x, y) = s.split_once(',')?;
+    Some((parse_coordinate(x)?, parse_coordinate(y)?))
+}
+
+/// A finite number; `inf` and `NaN` parse as floats but can't place a window
+fn parse_coordinate(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Parse a screen rectangle given as `WIDTHxHEIGHT` (origin 0,0) or
+/// `X,Y,WIDTHxHEIGHT`, e.g. `1920x1080` or `-1920,0,1920x1080`
+pub fn parse_screen(s: &str) -> Option<Frame> {
+    let (origin, size) = match s.rsplit_once(',') {
+        Some((origin, size)) => (parse_position(origin)?, size),
+        None => ((0.0, 0.0), s),
+    };
+    let (width, height) = parse_size(size)?;
+    Some(Frame::new(origin.0, origin.1, width, height))
+}
@@ -1,15 +1,28 @@
 // ABOUTME: Filesystem operations for reading/writing macOS Stickies data
-// ABOUTME: Handles plist parsing and RTFD bundle I/O
+// ABOUTME: Handles plist parsing, window frames, RTFD bundle I/O and quitting Stickies.app for edits
 
 use crate::{Result, StickyError};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub mod app;
+pub mod color;
+pub mod frame;
 pub mod plist;
 pub mod rtfd;
 
-pub use plist::StickyMetadata;
+pub use color::StickyColor;
+pub use frame::Frame;
+pub use plist::{StickyMetadata, WindowState};
 pub use rtfd::RtfdBundle;
 
+/// State file Stickies.app keeps next to the bundles (array format)
+pub const SAVED_STATE_FILE: &str = ".SavedStickiesState";
+
+/// Path of the RTFD bundle for a sticky
+pub fn bundle_path(stickies_path: &Path, uuid: &str) -> PathBuf {
+    stickies_path.join(format!("{}.rtfd", uuid))
+}
+
 /// Get the path to the Stickies directory, validating it exists
 pub fn stickies_dir() -> Result<PathBuf> {
     let home =
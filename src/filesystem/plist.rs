@@ -1,54 +1,189 @@
 // ABOUTME: Parser for StickiesState.plist metadata
 // ABOUTME: Extracts color, position, and window state from plist dictionaries
 
+use super::color::StickyColor;
+use super::frame::Frame;
 use crate::{Result, StickyError};
 use plist::{Dictionary, Value};
 use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
 use std::path::Path;
 
+// Keys Stickies.app writes for each note
+const KEY_COLOR: &str = "Color";
+const KEY_FRAME: &str = "Frame";
+const KEY_EXPANDED_FRAME: &str = "ExpandedFrame";
+const KEY_FLOATING: &str = "Floating";
+const KEY_COLLAPSED: &str = "Collapsed";
+const KEY_TRANSLUCENT: &str = "Translucent";
+const KEY_CUSTOM_COLOR: &str = "CustomColor";
+const KEY_FONT_NAME: &str = "FontName";
+const KEY_FONT_SIZE: &str = "FontSize";
+
 #[derive(Debug, Clone)]
 pub struct StickyMetadata {
     pub color_index: i64,
-    pub frame: String,
+    pub frame: Frame,
+    /// Frame to return to when a collapsed sticky is expanded again
+    pub expanded_frame: Option<Frame>,
+    pub is_floating: bool,
+    pub is_collapsed: bool,
+    pub is_translucent: bool,
+    /// Custom background color chosen in the Stickies color panel
+    pub custom_color: Option<String>,
+    pub font_name: Option<String>,
+    pub font_size: Option<f64>,
+    /// The complete plist dictionary for this sticky, including keys we don't
+    /// interpret, so it can be written back verbatim
+    pub raw: Dictionary,
+}
+
+/// Every window setting Stickies.app keeps for a sticky. Two machines showing
+/// the same note differently have different window states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowState {
+    pub color_index: i64,
+    pub frame: Frame,
+    pub expanded_frame: Option<Frame>,
     pub is_floating: bool,
+    pub is_collapsed: bool,
+    pub is_translucent: bool,
+    pub custom_color: Option<String>,
+    pub font_name: Option<String>,
+    pub font_size: Option<f64>,
 }
 
 impl StickyMetadata {
+    /// Metadata for a sticky that has no plist entry yet
+    pub fn new(color_index: i64) -> Self {
+        let mut raw = Dictionary::new();
+        raw.insert(KEY_COLOR.to_string(), Value::Integer(color_index.into()));
+        raw.insert(
+            KEY_FRAME.to_string(),
+            Value::String(Frame::default().to_string()),
+        );
+        raw.insert(KEY_FLOATING.to_string(), Value::Boolean(false));
+
+        Self {
+            color_index,
+            frame: Frame::default(),
+            expanded_frame: None,
+            is_floating: false,
+            is_collapsed: false,
+            is_translucent: false,
+            custom_color: None,
+            font_name: None,
+            font_size: None,
+            raw,
+        }
+    }
+
     pub fn from_plist_dict(dict: &Dictionary) -> Result<Self> {
         let color_index = dict
-            .get("Color")
+            .get(KEY_COLOR)
             .and_then(|v| v.as_signed_integer())
             .unwrap_or(0);
 
+        // An unparsable frame falls back to the default but stays untouched in `raw`
         let frame = dict
-            .get("Frame")
+            .get(KEY_FRAME)
+            .and_then(|v| v.as_string())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let expanded_frame = dict
+            .get(KEY_EXPANDED_FRAME)
             .and_then(|v| v.as_string())
-            .unwrap_or("{{100, 100}, {250, 250}}")
-            .to_string();
+            .and_then(|s| s.parse().ok());
 
-        let is_floating = dict
-            .get("Floating")
-            .and_then(|v| v.as_boolean())
-            .unwrap_or(false);
+        let flag = |key: &str| dict.get(key).and_then(|v| v.as_boolean()).unwrap_or(false);
+        let string = |key: &str| dict.get(key).and_then(|v| v.as_string()).map(String::from);
+
+        let font_size = dict.get(KEY_FONT_SIZE).and_then(|v| {
+            v.as_real()
+                .or_else(|| v.as_signed_integer().map(|i| i as f64))
+        });
 
         Ok(Self {
             color_index,
             frame,
-            is_floating,
+            expanded_frame,
+            is_floating: flag(KEY_FLOATING),
+            is_collapsed: flag(KEY_COLLAPSED),
+            is_translucent: flag(KEY_TRANSLUCENT),
+            custom_color: string(KEY_CUSTOM_COLOR),
+            font_name: string(KEY_FONT_NAME),
+            font_size,
+            raw: dict.clone(),
         })
     }
 
-    pub fn color_name(&self) -> &str {
-        match self.color_index {
-            0 => "yellow",
-            1 => "blue",
-            2 => "green",
-            3 => "pink",
-            4 => "purple",
-            5 => "gray",
-            _ => "yellow",
+    /// The typed window settings, for comparing one machine's view of a
+    /// sticky with another's
+    pub fn window_state(&self) -> WindowState {
+        WindowState {
+            color_index: self.color_index,
+            frame: self.frame,
+            expanded_frame: self.expanded_frame,
+            is_floating: self.is_floating,
+            is_collapsed: self.is_collapsed,
+            is_translucent: self.is_translucent,
+            custom_color: self.custom_color.clone(),
+            font_name: self.font_name.clone(),
+            font_size: self.font_size,
         }
     }
+
+    /// Move or resize the window, keeping the raw dictionary in step
+    pub fn set_frame(&mut self, frame: Frame) {
+        self.frame = frame;
+        self.raw
+            .insert(KEY_FRAME.to_string(), Value::String(frame.to_string()));
+    }
+
+    pub fn set_floating(&mut self, is_floating: bool) {
+        self.is_floating = is_floating;
+        self.raw
+            .insert(KEY_FLOATING.to_string(), Value::Boolean(is_floating));
+    }
+
+    pub fn set_collapsed(&mut self, is_collapsed: bool) {
+        self.is_collapsed = is_collapsed;
+        self.raw
+            .insert(KEY_COLLAPSED.to_string(), Value::Boolean(is_collapsed));
+    }
+
+    pub fn set_translucent(&mut self, is_translucent: bool) {
+        self.is_translucent = is_translucent;
+        self.raw
+            .insert(KEY_TRANSLUCENT.to_string(), Value::Boolean(is_translucent));
+    }
+
+    /// Serialize the full plist dictionary for storage in the database
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        Value::Dictionary(self.raw.clone()).to_writer_binary(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Restore metadata previously stored with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let value = Value::from_reader(Cursor::new(bytes))?;
+        let dict = value
+            .as_dictionary()
+            .ok_or_else(|| StickyError::Config("Invalid stored plist metadata".into()))?;
+        Self::from_plist_dict(dict)
+    }
+
+    pub fn color(&self) -> StickyColor {
+        StickyColor::from_index(self.color_index)
+    }
+
+    /// Name stored in the database `color` column, e.g. "pink" or "color-9"
+    pub fn color_name(&self) -> String {
+        self.color().to_string()
+    }
 }
 
 pub fn read_stickies_state(path: &Path) -> Result<HashMap<String, StickyMetadata>> {
@@ -91,3 +226,75 @@ pub fn read_stickies_state(path: &Path) -> Result<HashMap<String, StickyMetadata
 
     Ok(result)
 }
+
+/// Write sticky dictionaries back into a Stickies state file, replacing the
+/// entries for the given UUIDs (matched case-insensitively) and appending any
+/// that are missing. Other entries are left untouched and the file keeps its
+/// original binary or XML encoding.
+pub fn update_stickies_state(path: &Path, updates: &HashMap<String, Dictionary>) -> Result<()> {
+    let (mut value, binary) = if path.exists() {
+        let binary = fs::read(path)?.starts_with(b"bplist");
+        (Value::from_file(path)?, binary)
+    } else {
+        (Value::Array(Vec::new()), true)
+    };
+
+    match &mut value {
+        // Array format (.SavedStickiesState): the UUID lives inside each entry
+        Value::Array(entries) => {
+            for (uuid, dict) in updates {
+                let mut dict = dict.clone();
+                let existing = entries
+                    .iter_mut()
+                    .find(|entry| entry_uuid(entry).is_some_and(|u| u.eq_ignore_ascii_case(uuid)));
+
+                match existing {
+                    Some(entry) => {
+                        // Keep the UUID spelling Stickies.app used for this entry
+                        let original = entry_uuid(entry).unwrap_or(uuid).to_string();
+                        dict.insert("UUID".to_string(), Value::String(original));
+                        *entry = Value::Dictionary(dict);
+                    }
+                    None => {
+                        dict.insert("UUID".to_string(), Value::String(uuid.to_uppercase()));
+                        entries.push(Value::Dictionary(dict));
+                    }
+                }
+            }
+        }
+        // Dictionary format (StickiesState.plist): keyed by UUID
+        Value::Dictionary(root) => {
+            for (uuid, dict) in updates {
+                let key = root
+                    .keys()
+                    .find(|k| k.eq_ignore_ascii_case(uuid))
+                    .cloned()
+                    .unwrap_or_else(|| uuid.clone());
+                let mut dict = dict.clone();
+                dict.remove("UUID");
+                root.insert(key, Value::Dictionary(dict));
+            }
+        }
+        _ => return Err(StickyError::Config("Invalid plist format".into())),
+    }
+
+    // Write to a sibling file and rename so Stickies.app never sees a partial file
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name));
+
+    if binary {
+        value.to_file_binary(&tmp_path)?;
+    } else {
+        value.to_file_xml(&tmp_path)?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+fn entry_uuid(entry: &Value) -> Option<&str> {
+    entry.as_dictionary()?.get("UUID")?.as_string()
+}
@@ -1,16 +1,52 @@
 // ABOUTME: RTFD bundle reader/writer for macOS rich text format directories
 // ABOUTME: Handles TXT.rtf files and embedded attachments like images
 
+use crate::sync::lock::process_is_running;
 use crate::{Result, StickyError};
-use std::fs;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::Path;
 
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
 #[derive(Debug, Clone)]
 pub struct Attachment {
     pub filename: String,
     pub content: Vec<u8>,
 }
 
+impl Attachment {
+    /// MIME type sniffed from the content's magic bytes, or guessed from the
+    /// extension when the bytes aren't recognized
+    pub fn mime_type(&self) -> &'static str {
+        crate::mime::detect_with_name(&self.content, &self.filename)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.content.len() as u64
+    }
+}
+
+/// Finder and AppleDouble files that are never part of a note
+const IGNORED_NAMES: &[&str] = &[".DS_Store", ".localized", "Icon\r"];
+const IGNORED_PREFIX: &str = "._";
+
+/// Attachments that don't line up with the references in TXT.rtf
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttachmentCheck {
+    /// Files in the bundle that the RTF never mentions
+    pub orphaned: Vec<String>,
+    /// Files the RTF mentions that aren't in the bundle
+    pub missing: Vec<String>,
+}
+
+impl AttachmentCheck {
+    pub fn is_ok(&self) -> bool {
+        self.orphaned.is_empty() && self.missing.is_empty()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RtfdBundle {
     pub rtf_data: Vec<u8>,
@@ -30,22 +66,8 @@ impl RtfdBundle {
         let rtf_data = fs::read(&rtf_path)?;
 
         let mut attachments = Vec::new();
-
-        for entry in fs::read_dir(rtfd_path)? {
-            let entry = entry?;
-            let filename = entry.file_name().to_string_lossy().to_string();
-
-            // Skip TXT.rtf
-            if filename == "TXT.rtf" {
-                continue;
-            }
-
-            // Read any other files as attachments
-            if entry.path().is_file() {
-                let content = fs::read(entry.path())?;
-                attachments.push(Attachment { filename, content });
-            }
-        }
+        read_attachments(rtfd_path, "", &mut attachments)?;
+        attachments.sort_by(|a, b| a.filename.cmp(&b.filename));
 
         Ok(Self {
             rtf_data,
@@ -53,35 +75,203 @@ impl RtfdBundle {
         })
     }
 
+    /// Compare the attachments named in the RTF with the files in the bundle
+    pub fn check_attachments(&self) -> AttachmentCheck {
+        let references =
+            crate::rtf::attachment_references(&String::from_utf8_lossy(&self.rtf_data));
+
+        // A reference may name a directory (file package) whose contents are
+        // stored as nested attachments
+        let covers = |reference: &str, filename: &str| {
+            filename == reference
+                || filename
+                    .strip_prefix(reference)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+
+        let missing = references
+            .iter()
+            .filter(|r| !self.attachments.iter().any(|a| covers(r, &a.filename)))
+            .cloned()
+            .collect();
+        let orphaned = self
+            .attachments
+            .iter()
+            .filter(|a| !references.iter().any(|r| covers(r, &a.filename)))
+            .map(|a| a.filename.clone())
+            .collect();
+
+        AttachmentCheck { orphaned, missing }
+    }
+
     pub fn modified_time(rtfd_path: &Path) -> Result<i64> {
+        Ok(Self::modified_time_ns(rtfd_path)?.div_euclid(NANOS_PER_SECOND))
+    }
+
+    /// Modification time of TXT.rtf in nanoseconds since the epoch
+    pub fn modified_time_ns(rtfd_path: &Path) -> Result<i64> {
         let rtf_path = rtfd_path.join("TXT.rtf");
         let metadata = fs::metadata(&rtf_path)?;
         let modified = metadata.modified()?;
         let timestamp = modified
             .duration_since(std::time::UNIX_EPOCH)
             .map_err(|e| StickyError::Io(std::io::Error::other(e)))?
-            .as_secs() as i64;
+            .as_nanos() as i64;
         Ok(timestamp)
     }
 
+    /// Stamp the bundle's TXT.rtf with a modification time (seconds since the
+    /// epoch) so a note written from the database doesn't look newer than it
+    pub fn set_modified_time(rtfd_path: &Path, timestamp: i64) -> Result<()> {
+        Self::set_modified_time_ns(rtfd_path, timestamp.saturating_mul(NANOS_PER_SECOND))
+    }
+
+    /// Like `set_modified_time`, in nanoseconds since the epoch
+    pub fn set_modified_time_ns(rtfd_path: &Path, timestamp_ns: i64) -> Result<()> {
+        let time =
+            std::time::UNIX_EPOCH + std::time::Duration::from_nanos(timestamp_ns.max(0) as u64);
+        let file = File::options()
+            .write(true)
+            .open(rtfd_path.join("TXT.rtf"))?;
+        file.set_modified(time)?;
+        Ok(())
+    }
+
+    /// SHA-256 over the RTF and every attachment, as lowercase hex. Attachments
+    /// are hashed in filename order so the directory listing order doesn't
+    /// matter, and lengths are included so content can't shift between parts.
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update((self.rtf_data.len() as u64).to_le_bytes());
+        hasher.update(&self.rtf_data);
+
+        let mut attachments: Vec<&Attachment> = self.attachments.iter().collect();
+        attachments.sort_by(|a, b| a.filename.cmp(&b.filename));
+        for attachment in attachments {
+            hasher.update((attachment.filename.len() as u64).to_le_bytes());
+            hasher.update(attachment.filename.as_bytes());
+            hasher.update((attachment.content.len() as u64).to_le_bytes());
+            hasher.update(&attachment.content);
+        }
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Write the bundle so that readers see either the old or the new note,
+    /// never a mix. Files go into a temporary sibling directory that is synced
+    /// to disk and then renamed into place; attachments that are no longer
+    /// part of the bundle disappear with the old directory.
+    ///
+    /// A directory can't be renamed over a non-empty one, so an existing
+    /// bundle is first moved aside. If the process dies between the two
+    /// renames the previous bundle is left next to it as `.<name>.old-<pid>`,
+    /// which `recover_interrupted_writes` puts back.
     pub fn write(&self, rtfd_path: &Path) -> Result<()> {
-        fs::create_dir_all(rtfd_path)?;
+        // Validate every filename before touching the disk
+        let filenames = self
+            .attachments
+            .iter()
+            .map(|a| Self::sanitize_filename(&a.filename))
+            .collect::<Result<Vec<_>>>()?;
 
-        let rtf_path = rtfd_path.join("TXT.rtf");
-        fs::write(&rtf_path, &self.rtf_data)?;
+        let parent = rtfd_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        fs::create_dir_all(parent)?;
 
-        for attachment in &self.attachments {
-            // Validate filename to prevent path traversal attacks
-            let safe_filename = Self::sanitize_filename(&attachment.filename)?;
-            let attachment_path = rtfd_path.join(safe_filename);
-            fs::write(&attachment_path, &attachment.content)?;
+        let name = rtfd_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| {
+                StickyError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "RTFD path has no file name",
+                ))
+            })?;
+        let pid = std::process::id();
+        let tmp_path = parent.join(format!(".{}.tmp-{}", name, pid));
+        let old_path = parent.join(format!(".{}.old-{}", name, pid));
+
+        // Leftovers from an earlier crashed write of this process id
+        if tmp_path.exists() {
+            fs::remove_dir_all(&tmp_path)?;
+        }
+        fs::create_dir(&tmp_path)?;
+
+        let staged = (|| -> Result<()> {
+            write_synced(&tmp_path.join("TXT.rtf"), &self.rtf_data)?;
+            for (attachment, filename) in self.attachments.iter().zip(&filenames) {
+                let path = tmp_path.join(filename);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                write_synced(&path, &attachment.content)?;
+            }
+            sync_dir(&tmp_path)
+        })();
+        if let Err(e) = staged {
+            let _ = fs::remove_dir_all(&tmp_path);
+            return Err(e);
+        }
+
+        if rtfd_path.exists() {
+            fs::rename(rtfd_path, &old_path)?;
+            if let Err(e) = fs::rename(&tmp_path, rtfd_path) {
+                // Put the previous bundle back rather than leave nothing
+                let _ = fs::rename(&old_path, rtfd_path);
+                let _ = fs::remove_dir_all(&tmp_path);
+                return Err(e.into());
+            }
+            sync_dir(parent)?;
+            fs::remove_dir_all(&old_path)?;
+        } else {
+            fs::rename(&tmp_path, rtfd_path)?;
+            sync_dir(parent)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clean up after writes that died part way through: a bundle that was
+    /// moved aside and never replaced is put back, and leftover staging and
+    /// moved-aside directories are deleted. Those of writes still running in
+    /// other processes are left alone.
+    pub fn recover_interrupted_writes(dir: &Path) -> Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+
+        let own_pid = std::process::id();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some((name, moved_aside, pid)) = parse_write_leftover(file_name) else {
+                continue;
+            };
+            if pid != own_pid && process_is_running(pid) {
+                continue;
+            }
+
+            let bundle = dir.join(name);
+            if moved_aside && !bundle.exists() {
+                fs::rename(&path, &bundle)?;
+            } else {
+                fs::remove_dir_all(&path)?;
+            }
         }
 
         Ok(())
     }
 
     /// Sanitize filename to prevent path traversal attacks
-    fn sanitize_filename(filename: &str) -> Result<&str> {
+    pub(crate) fn sanitize_filename(filename: &str) -> Result<&str> {
         // Reject empty filenames
         if filename.is_empty() {
             return Err(StickyError::Io(std::io::Error::new(
@@ -98,19 +288,22 @@ impl RtfdBundle {
             )));
         }
 
-        // Reject path traversal sequences
-        if filename.contains("..") {
+        // Reject Windows separators; '/' separates nested attachment directories
+        if filename.contains('\\') {
             return Err(StickyError::Io(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
-                "Attachment filename cannot contain '..'",
+                "Attachment filename cannot contain '\\'",
             )));
         }
 
-        // Reject directory separators (filename must be in same directory)
-        if filename.contains('/') || filename.contains('\\') {
+        // Reject path traversal and empty path components
+        if filename
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..")
+        {
             return Err(StickyError::Io(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
-                "Attachment filename cannot contain directory separators",
+                "Attachment filename cannot contain '..', '.' or empty path components",
             )));
         }
 
@@ -133,3 +326,64 @@ impl RtfdBundle {
         }
     }
 }
+
+fn is_ignored(name: &str) -> bool {
+    IGNORED_NAMES.contains(&name) || name.starts_with(IGNORED_PREFIX)
+}
+
+/// Collect every file below `dir` as an attachment named by its path relative
+/// to the bundle, e.g. `Folder/image.png`
+fn read_attachments(dir: &Path, prefix: &str, attachments: &mut Vec<Attachment>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if is_ignored(&name) || (prefix.is_empty() && name == "TXT.rtf") {
+            continue;
+        }
+
+        let filename = format!("{}{}", prefix, name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            read_attachments(&entry.path(), &format!("{}/", filename), attachments)?;
+        } else if file_type.is_file() {
+            let content = fs::read(entry.path())?;
+            attachments.push(Attachment { filename, content });
+        }
+    }
+    Ok(())
+}
+
+/// Write a file and flush it to disk before returning
+fn write_synced(path: &Path, data: &[u8]) -> Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Flush a directory's entries (new names, renames) to disk
+fn sync_dir(path: &Path) -> Result<()> {
+    // Directories can't be opened for syncing on every platform; the data
+    // itself has already been synced, so this is best effort
+    if let Ok(dir) = File::open(path) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Split `.<name>.old-<pid>` or `.<name>.tmp-<pid>`, left by `RtfdBundle::write`,
+/// into the bundle name, whether it is a moved-aside bundle and the writer's pid
+fn parse_write_leftover(file_name: &str) -> Option<(&str, bool, u32)> {
+    let rest = file_name.strip_prefix('.')?;
+    let (name, moved_aside, pid) = if let Some((name, pid)) = rest.rsplit_once(".old-") {
+        (name, true, pid)
+    } else {
+        let (name, pid) = rest.rsplit_once(".tmp-")?;
+        (name, false, pid)
+    };
+    if !name.ends_with(".rtfd") {
+        return None;
+    }
+    Some((name, moved_aside, pid.parse().ok()?))
+}
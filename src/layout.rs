@@ -0,0 +1,301 @@
+// ABOUTME: Window layout operations across all stickies
+// ABOUTME: Snapshots, restores and auto-arranges frames via the Stickies state file and database
+
+use crate::database::{Database, LayoutEntry};
+use crate::filesystem::{self, app, plist, Frame, StickyColor, StickyMetadata};
+use crate::{Result, StickyError};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Space left between arranged windows and around the screen edge
+const GAP: f64 = 10.0;
+
+/// Offset between successive windows in a cascade
+const CASCADE_STEP: f64 = 24.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arrangement {
+    /// Tile windows in an evenly sized grid
+    Grid,
+    /// Overlap windows diagonally from the top-left corner
+    Cascade,
+    /// One column per color, windows stacked within each column
+    ByColor,
+}
+
+/// Capture the current window state of every sticky on this machine
+pub fn snapshot(stickies_path: &Path) -> Result<Vec<LayoutEntry>> {
+    let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+    let metadata_map = plist::read_stickies_state(&plist_path)?;
+
+    let mut entries: Vec<LayoutEntry> = metadata_map
+        .into_iter()
+        .map(|(uuid, metadata)| LayoutEntry {
+            uuid,
+            frame: metadata.frame,
+            is_floating: metadata.is_floating,
+            is_collapsed: metadata.is_collapsed,
+        })
+        .collect();
+    entries.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+
+    Ok(entries)
+}
+
+/// Apply a saved layout to the stickies on this machine, optionally clamping
+/// every frame to `screen`. Stickies in the layout that don't exist here are
+/// skipped. Returns the number of stickies updated.
+pub fn restore(
+    db: &Database,
+    stickies_path: &Path,
+    machine: &str,
+    entries: &[LayoutEntry],
+    screen: Option<&Frame>,
+) -> Result<usize> {
+    let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+    let mut metadata_map = plist::read_stickies_state(&plist_path)?;
+
+    let mut updates = HashMap::new();
+
+    for entry in entries {
+        if let Some(mut metadata) = metadata_map.remove(&entry.uuid) {
+            let frame = match screen {
+                Some(screen) => entry.frame.clamped_to(screen),
+                None => entry.frame,
+            };
+            metadata.set_frame(frame);
+            metadata.set_floating(entry.is_floating);
+            metadata.set_collapsed(entry.is_collapsed);
+            updates.insert(entry.uuid.clone(), metadata);
+        }
+    }
+
+    let count = updates.len();
+    apply_metadata(db, stickies_path, machine, updates)?;
+    Ok(count)
+}
+
+/// Write changed window metadata to the Stickies state file and to the
+/// database rows of stickies that have been synced. The two now agree, so the
+/// new state is recorded as synced for `machine`; otherwise its next sync
+/// would see the edit as made in the state file and undo later changes.
+pub fn apply_metadata(
+    db: &Database,
+    stickies_path: &Path,
+    machine: &str,
+    updates: HashMap<String, StickyMetadata>,
+) -> Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+    let raw_updates = updates
+        .iter()
+        .map(|(uuid, metadata)| (uuid.clone(), metadata.raw.clone()))
+        .collect();
+    app::while_quit(false, || {
+        plist::update_stickies_state(&plist_path, &raw_updates)
+    })?;
+
+    let mut layouts = Vec::new();
+    for (uuid, metadata) in &updates {
+        db.update_metadata(uuid, metadata)?;
+        layouts.push((uuid.clone(), metadata.to_bytes()?));
+    }
+
+    db.save_synced_layouts(machine, &layouts)
+}
+
+/// Move and/or resize one sticky. The database always takes the new frame so
+/// other machines pick it up on their next sync; the Stickies state file in
+/// `stickies_path` is updated too when it has the sticky. Returns the new
+/// frame and whether the state file was written.
+pub fn set_frame(
+    db: &Database,
+    stickies_path: Option<&Path>,
+    machine: &str,
+    uuid: &str,
+    size: Option<(f64, f64)>,
+    position: Option<(f64, f64)>,
+) -> Result<(Frame, bool)> {
+    let sticky = db
+        .get_sticky(uuid)?
+        .ok_or_else(|| StickyError::NotFound(format!("Sticky with UUID {} not found", uuid)))?;
+
+    let mut metadata = if sticky.plist_metadata.is_empty() {
+        let color: StickyColor = sticky.color.parse().unwrap_or_default();
+        StickyMetadata::new(color.index())
+    } else {
+        StickyMetadata::from_bytes(&sticky.plist_metadata)?
+    };
+
+    let mut new_frame = metadata.frame;
+    if let Some((width, height)) = size {
+        new_frame = new_frame.with_size(width, height);
+    }
+    if let Some((x, y)) = position {
+        new_frame = new_frame.with_position(x, y);
+    }
+    metadata.set_frame(new_frame);
+
+    let local = match stickies_path {
+        Some(stickies_path) => {
+            let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+            plist::read_stickies_state(&plist_path)?.contains_key(uuid)
+        }
+        None => false,
+    };
+    match stickies_path {
+        Some(stickies_path) if local => {
+            let updates = HashMap::from([(uuid.to_string(), metadata)]);
+            apply_metadata(db, stickies_path, machine, updates)?
+        }
+        _ => db.update_metadata(uuid, &metadata)?,
+    }
+
+    Ok((new_frame, local))
+}
+
+/// Arrange the stickies on this machine within `screen`, limited to the UUIDs
+/// in `only` when given. Returns the number of stickies moved.
+pub fn arrange_stickies(
+    db: &Database,
+    stickies_path: &Path,
+    machine: &str,
+    arrangement: Arrangement,
+    screen: &Frame,
+    only: Option<&HashSet<String>>,
+) -> Result<usize> {
+    let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+    let mut stickies: Vec<(String, StickyMetadata)> = plist::read_stickies_state(&plist_path)?
+        .into_iter()
+        .filter(|(uuid, _)| only.is_none_or(|only| only.contains(uuid)))
+        .collect();
+    // Sort so repeated runs produce the same arrangement
+    stickies.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let frames = arrange(&stickies, arrangement, screen);
+
+    let mut metadata_map: HashMap<String, StickyMetadata> = stickies.into_iter().collect();
+    let mut updates = HashMap::new();
+    for (uuid, frame) in frames {
+        if let Some(mut metadata) = metadata_map.remove(&uuid) {
+            metadata.set_frame(frame);
+            updates.insert(uuid, metadata);
+        }
+    }
+
+    let count = updates.len();
+    apply_metadata(db, stickies_path, machine, updates)?;
+    Ok(count)
+}
+
+/// Compute new frames for the given stickies within `screen`. Stickies are
+/// placed in the order given; windows keep their size unless it has to shrink
+/// to fit their slot.
+pub fn arrange(
+    stickies: &[(String, StickyMetadata)],
+    arrangement: Arrangement,
+    screen: &Frame,
+) -> Vec<(String, Frame)> {
+    match arrangement {
+        Arrangement::Grid => {
+            let frames: Vec<Frame> = stickies.iter().map(|(_, m)| m.frame).collect();
+            let uuids = stickies.iter().map(|(uuid, _)| uuid.clone());
+            uuids.zip(grid(&frames, screen)).collect()
+        }
+        Arrangement::Cascade => stickies
+            .iter()
+            .enumerate()
+            .map(|(i, (uuid, metadata))| (uuid.clone(), cascade(i, metadata.frame, screen)))
+            .collect(),
+        Arrangement::ByColor => by_color(stickies, screen),
+    }
+}
+
+/// Lay frames out in a grid of equal cells, filling rows from the top
+fn grid(frames: &[Frame], screen: &Frame) -> Vec<Frame> {
+    if frames.is_empty() {
+        return Vec::new();
+    }
+
+    let count = frames.len();
+    let columns = (count as f64).sqrt().ceil() as usize;
+    let rows = count.div_ceil(columns);
+    let cell_width = screen.width / columns as f64;
+    let cell_height = screen.height / rows as f64;
+
+    frames
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            let cell = Frame::new(
+                screen.x + (i % columns) as f64 * cell_width,
+                // Cocoa's origin is bottom-left, so row 0 is the highest y
+                screen.y + screen.height - ((i / columns) + 1) as f64 * cell_height,
+                cell_width,
+                cell_height,
+            );
+            place_in_cell(*frame, &cell)
+        })
+        .collect()
+}
+
+fn cascade(index: usize, frame: Frame, screen: &Frame) -> Frame {
+    // Start a new diagonal once a window would run off the screen
+    let fits = ((screen.width - frame.width).min(screen.height - frame.height) / CASCADE_STEP)
+        .floor()
+        .max(0.0) as usize
+        + 1;
+    let step = (index % fits) as f64 * CASCADE_STEP;
+    let x = screen.x + GAP + step + (index / fits) as f64 * CASCADE_STEP * 2.0;
+    let y = screen.y + screen.height - frame.height - GAP - step;
+
+    Frame::new(x, y, frame.width, frame.height).clamped_to(screen)
+}
+
+fn by_color(stickies: &[(String, StickyMetadata)], screen: &Frame) -> Vec<(String, Frame)> {
+    let mut colors: Vec<i64> = stickies.iter().map(|(_, m)| m.color_index).collect();
+    colors.sort();
+    colors.dedup();
+
+    if colors.is_empty() {
+        return Vec::new();
+    }
+
+    let column_width = screen.width / colors.len() as f64;
+    let mut result = Vec::new();
+
+    for (column, color) in colors.iter().enumerate() {
+        let group: Vec<_> = stickies
+            .iter()
+            .filter(|(_, m)| m.color_index == *color)
+            .collect();
+        let cell_height = screen.height / group.len() as f64;
+
+        for (row, (uuid, metadata)) in group.into_iter().enumerate() {
+            let cell = Frame::new(
+                screen.x + column as f64 * column_width,
+                screen.y + screen.height - (row + 1) as f64 * cell_height,
+                column_width,
+                cell_height,
+            );
+            result.push((uuid.clone(), place_in_cell(metadata.frame, &cell)));
+        }
+    }
+
+    result
+}
+
+/// Put a window at the top-left of a cell, shrinking it to fit inside the gaps
+fn place_in_cell(frame: Frame, cell: &Frame) -> Frame {
+    let width = frame.width.min(cell.width - 2.0 * GAP).max(1.0);
+    let height = frame.height.min(cell.height - 2.0 * GAP).max(1.0);
+    Frame::new(
+        cell.x + GAP,
+        cell.y + cell.height - height - GAP,
+        width,
+        height,
+    )
+}
@@ -1,12 +1,17 @@
 // ABOUTME: Library root for sticky-situation - macOS Stickies sync tool
-// ABOUTME: Exports public modules for configuration, database, filesystem, and sync
+// ABOUTME: Exports public modules for configuration, database, filesystem, layouts, search, tags, sync, and version vectors
 
 pub mod config;
 pub mod database;
 pub mod error;
 pub mod filesystem;
+pub mod layout;
+pub mod mime;
+pub mod query;
 pub mod rtf;
 pub mod sync;
+pub mod tags;
+pub mod version_vector;
 
 pub use error::StickyError;
 pub type Result<T> = std::result::Result<T, StickyError>;
@@ -1,7 +1,9 @@
 // ABOUTME: CLI entry point for sticky-situation
-// ABOUTME: Dispatches to sync, new, and search subcommands
+// ABOUTME: Dispatches to sync, watch, merge, quarantine, new, search, list, show, set, tag, layout, arrange, hup, and config subcommands
 
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
+use std::path::PathBuf;
+use sticky_situation::layout::Arrangement;
 use sticky_situation::Result;
 
 mod commands;
@@ -25,25 +27,84 @@ enum Commands {
         verbose: bool,
     },
 
+    /// Keep syncing in the background whenever stickies change
+    Watch {
+        /// Milliseconds to wait after the last edit before syncing
+        #[arg(long, default_value_t = 500)]
+        debounce_ms: u64,
+
+        /// Seconds between checks for database changes from other machines
+        #[arg(long, default_value_t = 5)]
+        poll_secs: u64,
+
+        #[arg(long, short)]
+        verbose: bool,
+    },
+
+    /// Merge another sticky-situation database into this one
+    ///
+    /// Stickies are only added or updated, never deleted. Deletions leave no
+    /// record in the database, so a sticky deleted here since the other copy
+    /// was made comes back from it.
+    Merge {
+        /// Database to merge from (e.g. a backup or another machine's copy)
+        other: PathBuf,
+
+        #[arg(long)]
+        dry_run: bool,
+
+        #[arg(long, short)]
+        verbose: bool,
+    },
+
+    /// List stickies that failed to sync on this machine
+    Quarantine {
+        /// Forget the quarantined stickies
+        #[arg(long)]
+        clear: bool,
+    },
+
     /// Create a new sticky note
     New {
         /// Text content of the sticky
         text: Option<String>,
     },
 
-    /// Search stickies by content
+    /// Search stickies by content and filters
     Search {
-        /// Search query
+        /// Search query, e.g. `"exact phrase" -excluded color:pink machine:work-laptop
+        /// modified:>2026-09-01 has:image is:floating`
+        #[arg(allow_hyphen_values = true)]
         query: String,
 
         #[arg(long)]
         color: Option<String>,
+
+        /// Maximum number of results to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        /// Number of results to skip (for paging)
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Pass the query to SQLite FTS5 unescaped (enables OR, NEAR, prefix* ...)
+        #[arg(long)]
+        fts: bool,
+
+        /// Show each sticky's whole content with matches marked, not just an excerpt
+        #[arg(long)]
+        full: bool,
     },
 
     /// List all stickies
     List {
         #[arg(long)]
         color: Option<String>,
+
+        /// Only list stickies with this tag
+        #[arg(long)]
+        tag: Option<String>,
     },
 
     /// Show a specific sticky by UUID
@@ -52,6 +113,64 @@ enum Commands {
         uuid: String,
     },
 
+    /// Change a sticky's window size or position
+    Set {
+        /// UUID of the sticky to change
+        uuid: String,
+
+        /// New size as WIDTHxHEIGHT, e.g. 300x200
+        #[arg(long)]
+        size: Option<String>,
+
+        /// New position as X,Y, e.g. 40,40
+        #[arg(long)]
+        position: Option<String>,
+    },
+
+    /// Show, add (+tag) or remove (-tag) manual tags on a sticky
+    Tag {
+        /// UUID of the sticky to tag
+        uuid: String,
+
+        /// Tag changes, e.g. `+work -personal`
+        #[arg(allow_hyphen_values = true)]
+        changes: Vec<String>,
+    },
+
+    /// Save and restore arrangements of sticky windows
+    Layout {
+        #[command(subcommand)]
+        command: LayoutCommands,
+    },
+
+    /// Automatically tile, cascade or group sticky windows on a screen
+    #[command(group(ArgGroup::new("mode").required(true).args(["grid", "cascade", "by_color"])))]
+    Arrange {
+        /// Tile windows in an evenly sized grid
+        #[arg(long)]
+        grid: bool,
+
+        /// Overlap windows diagonally from the top-left corner
+        #[arg(long)]
+        cascade: bool,
+
+        /// Put each color in its own column
+        #[arg(long)]
+        by_color: bool,
+
+        /// Screen to arrange on, as WIDTHxHEIGHT or X,Y,WIDTHxHEIGHT
+        #[arg(long)]
+        screen: String,
+
+        /// Only arrange stickies of this color
+        #[arg(long)]
+        color: Option<String>,
+
+        /// Only arrange stickies with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+
     /// Send HUP signal to reload Stickies.app
     Hup,
 
@@ -63,15 +182,98 @@ enum Commands {
     },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum LayoutCommands {
+    /// Save every sticky's frame, floating and collapsed state under a name
+    Save {
+        /// Name of the layout, e.g. "laptop" or "desk"
+        name: String,
+    },
+
+    /// Write a saved layout back to Stickies.app
+    Restore {
+        /// Name of the layout to restore
+        name: String,
+
+        /// Keep windows on a screen given as WIDTHxHEIGHT or X,Y,WIDTHxHEIGHT
+        #[arg(long)]
+        clamp: Option<String>,
+    },
+
+    /// List saved layouts
+    List,
+
+    /// Delete a saved layout
+    Delete {
+        /// Name of the layout to delete
+        name: String,
+    },
+}
+
+fn main() {
+    // Returning the error from main would print its Debug form
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
 
+fn run(cli: Cli) -> Result<()> {
     match cli.command {
         Commands::Sync { dry_run, verbose } => commands::sync::run(dry_run, verbose),
+        Commands::Watch {
+            debounce_ms,
+            poll_secs,
+            verbose,
+        } => commands::watch::run(debounce_ms, poll_secs, verbose),
+        Commands::Merge {
+            other,
+            dry_run,
+            verbose,
+        } => commands::merge::run(&other, dry_run, verbose),
+        Commands::Quarantine { clear } => commands::quarantine::run(clear),
         Commands::New { text } => commands::new::run(text),
-        Commands::Search { query, color } => commands::search::run(&query, color.as_deref()),
-        Commands::List { color } => commands::list::run(color.as_deref()),
+        Commands::Search {
+            query,
+            color,
+            limit,
+            offset,
+            fts,
+            full,
+        } => commands::search::run(&query, color.as_deref(), limit, offset, fts, full),
+        Commands::List { color, tag } => commands::list::run(color.as_deref(), tag.as_deref()),
         Commands::Show { uuid } => commands::show::run(&uuid),
+        Commands::Set {
+            uuid,
+            size,
+            position,
+        } => commands::set::run(&uuid, size.as_deref(), position.as_deref()),
+        Commands::Tag { uuid, changes } => commands::tag::run(&uuid, &changes),
+        Commands::Layout { command } => match command {
+            LayoutCommands::Save { name } => commands::layout::save(&name),
+            LayoutCommands::Restore { name, clamp } => {
+                commands::layout::restore(&name, clamp.as_deref())
+            }
+            LayoutCommands::List => commands::layout::list(),
+            LayoutCommands::Delete { name } => commands::layout::delete(&name),
+        },
+        Commands::Arrange {
+            grid,
+            cascade,
+            by_color: _,
+            screen,
+            color,
+            tag,
+        } => {
+            let arrangement = if grid {
+                Arrangement::Grid
+            } else if cascade {
+                Arrangement::Cascade
+            } else {
+                Arrangement::ByColor
+            };
+            commands::arrange::run(arrangement, &screen, color.as_deref(), tag.as_deref())
+        }
         Commands::Hup => commands::hup::run(),
         Commands::Config { edit } => commands::config::run(edit),
     }
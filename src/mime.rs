@@ -0,0 +1,74 @@
+// ABOUTME: MIME type detection for sticky attachments
+// ABOUTME: Sniffs magic bytes so images and media are recognized regardless of filename
+
+/// Fallback for content we don't recognize
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Detect the MIME type of attachment content from its leading bytes
+pub fn detect(content: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"II*\0", "image/tiff"),
+        (b"MM\0*", "image/tiff"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"{\\rtf", "text/rtf"),
+    ];
+
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(magic, _)| content.starts_with(magic))
+    {
+        return mime;
+    }
+
+    if content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    // ISO base media files (HEIC photos, MP4 and QuickTime movies) carry a
+    // brand after the "ftyp" box header
+    if content.len() >= 12 && &content[4..8] == b"ftyp" {
+        return match &content[8..12] {
+            b"heic" | b"heix" | b"heim" | b"heis" | b"mif1" | b"msf1" => "image/heic",
+            b"qt  " => "video/quicktime",
+            _ => "video/mp4",
+        };
+    }
+
+    OCTET_STREAM
+}
+
+/// Detect the MIME type from content, falling back to the filename extension
+/// when the bytes aren't recognized
+pub fn detect_with_name(content: &[u8], filename: &str) -> &'static str {
+    match detect(content) {
+        OCTET_STREAM => from_extension(filename).unwrap_or(OCTET_STREAM),
+        mime => mime,
+    }
+}
+
+fn from_extension(filename: &str) -> Option<&'static str> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    let mime = match extension.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "tif" | "tiff" => "image/tiff",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "pdf" => "application/pdf",
+        "mov" => "video/quicktime",
+        "mp4" | "m4v" => "video/mp4",
+        "zip" => "application/zip",
+        "txt" => "text/plain",
+        "rtf" => "text/rtf",
+        _ => return None,
+    };
+    Some(mime)
+}
@@ -0,0 +1,435 @@
+// ABOUTME: Structured search query language for `sticky search`
+// ABOUTME: Parses filters like color:pink, tag:work or modified:>2026-09-01 into an AST compiled to SQL
+
+use crate::filesystem::StickyColor;
+use crate::{tags, Result, StickyError};
+use rusqlite::types::Value;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// File extensions treated as images by `has:image` for attachments stored
+/// before MIME types were recorded
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "tif", "tiff", "heic", "bmp", "webp",
+];
+
+/// A parsed search query: every term must match for a sticky to be returned
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchQuery {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// A single word matched against the full-text index
+    Word(String),
+    /// A quoted phrase matched exactly against the full-text index
+    Phrase(String),
+    /// An FTS5 expression passed through unescaped (`--fts` mode)
+    Raw(String),
+    Filter(Filter),
+    /// A term prefixed with `-`, excluding stickies that match it
+    Not(Box<Term>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Color(StickyColor),
+    Machine(String),
+    Tag(String),
+    Modified(DateFilter),
+    Created(DateFilter),
+    Has(HasFilter),
+    Is(IsFilter),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Before,
+    OnOrBefore,
+    After,
+    OnOrAfter,
+    On,
+}
+
+/// A comparison against a calendar day (UTC), e.g. `>2026-09-01`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DateFilter {
+    pub comparison: Comparison,
+    /// Unix timestamp of midnight UTC on the given day
+    pub day_start: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HasFilter {
+    Image,
+    Attachment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IsFilter {
+    Floating,
+}
+
+/// A query lowered to SQL over the `stickies s` alias
+#[derive(Debug, Clone, Default)]
+pub struct CompiledQuery {
+    /// FTS5 MATCH expression for the positive text terms, if any
+    pub fts_match: Option<String>,
+    /// SQL conditions ANDed together in the WHERE clause
+    pub conditions: Vec<String>,
+    /// Positional parameters for the `?` placeholders in `conditions`
+    pub params: Vec<Value>,
+    /// Whether the MATCH expression contains FTS5 syntax typed by the user
+    pub raw: bool,
+}
+
+/// A token before filter keys are interpreted
+enum RawToken {
+    Word(String),
+    Phrase(String),
+    KeyValue(String, String),
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut terms = Vec::new();
+
+        for (negated, token) in tokenize(input) {
+            let term = match token {
+                RawToken::Word(word) => Term::Word(word),
+                RawToken::Phrase(phrase) => Term::Phrase(phrase),
+                RawToken::KeyValue(key, value) => match parse_filter(&key, &value)? {
+                    Some(filter) => Term::Filter(filter),
+                    // Unknown keys (e.g. "http:") are plain search text
+                    None => Term::Word(format!("{}:{}", key, value)),
+                },
+            };
+
+            terms.push(if negated {
+                Term::Not(Box::new(term))
+            } else {
+                term
+            });
+        }
+
+        Ok(Self { terms })
+    }
+
+    /// A query passed to FTS5 verbatim, for users who want its operators
+    /// (`OR`, `NEAR`, column filters, ...)
+    pub fn raw(input: &str) -> Self {
+        Self {
+            terms: vec![Term::Raw(input.to_string())],
+        }
+    }
+
+    pub fn push(&mut self, term: Term) {
+        self.terms.push(term);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn compile(&self) -> CompiledQuery {
+        let mut compiled = CompiledQuery::default();
+        let mut text_terms = Vec::new();
+
+        for term in &self.terms {
+            if let Term::Raw(_) = term {
+                compiled.raw = true;
+            }
+            match term {
+                Term::Word(_) | Term::Phrase(_) | Term::Raw(_) => {
+                    text_terms.push(fts_expression(term))
+                }
+                Term::Filter(filter) => {
+                    let condition = compile_filter(filter, &mut compiled.params);
+                    compiled.conditions.push(condition);
+                }
+                Term::Not(inner) => match inner.as_ref() {
+                    Term::Filter(filter) => {
+                        let condition = compile_filter(filter, &mut compiled.params);
+                        compiled.conditions.push(format!("NOT ({})", condition));
+                    }
+                    other => {
+                        compiled.conditions.push(
+                            "s.uuid NOT IN (SELECT uuid FROM stickies_fts WHERE stickies_fts MATCH ?)"
+                                .to_string(),
+                        );
+                        compiled.params.push(Value::Text(fts_expression(other)));
+                    }
+                },
+            }
+        }
+
+        if !text_terms.is_empty() {
+            compiled.fts_match = Some(text_terms.join(" "));
+        }
+
+        compiled
+    }
+}
+
+fn tokenize(input: &str) -> Vec<(bool, RawToken)> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut negated = false;
+        if c == '-' {
+            chars.next();
+            match chars.peek() {
+                Some(next) if !next.is_whitespace() => negated = true,
+                // A lone "-" is just text
+                _ => {
+                    tokens.push((false, RawToken::Word("-".to_string())));
+                    continue;
+                }
+            }
+        }
+
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            tokens.push((negated, RawToken::Phrase(read_quoted(&mut chars))));
+            continue;
+        }
+
+        let mut word = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            word.push(c);
+
+            // Allow key:"quoted value"
+            if c == ':' && chars.peek() == Some(&'"') {
+                chars.next();
+                word.push_str(&read_quoted(&mut chars));
+                break;
+            }
+        }
+
+        let token = match word.split_once(':') {
+            Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+                RawToken::KeyValue(key.to_lowercase(), value.to_string())
+            }
+            _ => RawToken::Word(word),
+        };
+        tokens.push((negated, token));
+    }
+
+    tokens
+}
+
+/// Read up to the closing quote (or end of input); the opening quote has been consumed
+fn read_quoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut value = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            break;
+        }
+        value.push(c);
+    }
+    value
+}
+
+fn parse_filter(key: &str, value: &str) -> Result<Option<Filter>> {
+    let filter = match key {
+        "color" => match value.parse() {
+            Ok(color) => Filter::Color(color),
+            Err(message) => {
+                return Err(StickyError::InvalidQuery {
+                    message,
+                    hint: "Use color:yellow, blue, green, pink, purple or gray".into(),
+                })
+            }
+        },
+        "machine" => Filter::Machine(value.to_string()),
+        "tag" => match tags::normalize_tag(value) {
+            Some(tag) => Filter::Tag(tag),
+            None => {
+                return Err(StickyError::InvalidQuery {
+                    message: format!("invalid tag 'tag:{}'", value),
+                    hint: "Tags contain only letters, digits, '_' and '-'".into(),
+                })
+            }
+        },
+        "modified" => Filter::Modified(parse_date_filter(key, value)?),
+        "created" => Filter::Created(parse_date_filter(key, value)?),
+        "has" => match value.to_lowercase().as_str() {
+            "image" | "images" => Filter::Has(HasFilter::Image),
+            "attachment" | "attachments" => Filter::Has(HasFilter::Attachment),
+            _ => {
+                return Err(StickyError::InvalidQuery {
+                    message: format!("unknown value 'has:{}'", value),
+                    hint: "Use has:image or has:attachment".into(),
+                })
+            }
+        },
+        "is" => match value.to_lowercase().as_str() {
+            "floating" => Filter::Is(IsFilter::Floating),
+            _ => {
+                return Err(StickyError::InvalidQuery {
+                    message: format!("unknown value 'is:{}'", value),
+                    hint: "Use is:floating".into(),
+                })
+            }
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(filter))
+}
+
+fn parse_date_filter(key: &str, value: &str) -> Result<DateFilter> {
+    let (comparison, date) = if let Some(rest) = value.strip_prefix(">=") {
+        (Comparison::OnOrAfter, rest)
+    } else if let Some(rest) = value.strip_prefix("<=") {
+        (Comparison::OnOrBefore, rest)
+    } else if let Some(rest) = value.strip_prefix('>') {
+        (Comparison::After, rest)
+    } else if let Some(rest) = value.strip_prefix('<') {
+        (Comparison::Before, rest)
+    } else {
+        (Comparison::On, value.strip_prefix('=').unwrap_or(value))
+    };
+
+    let day_start = parse_date(date).ok_or_else(|| StickyError::InvalidQuery {
+        message: format!("invalid date in '{}:{}'", key, value),
+        hint: format!("Dates look like {}:>2026-09-01 (YYYY-MM-DD)", key),
+    })?;
+
+    Ok(DateFilter {
+        comparison,
+        day_start,
+    })
+}
+
+/// Parse a YYYY-MM-DD date into the Unix timestamp of midnight UTC
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Escape user text so FTS5 treats it literally: each whitespace-separated
+/// word becomes a quoted string, so `foo-bar` or `c++` never hit the FTS5
+/// expression parser
+pub fn escape_fts(text: &str) -> String {
+    text.split_whitespace()
+        .map(quote_word)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote_fts(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Quote a single word, keeping a trailing `*` as a prefix search
+fn quote_word(word: &str) -> String {
+    match word.strip_suffix('*') {
+        Some(stem) if !stem.is_empty() => format!("{}*", quote_fts(stem)),
+        _ => quote_fts(word),
+    }
+}
+
+fn fts_expression(term: &Term) -> String {
+    match term {
+        Term::Word(word) => quote_word(word),
+        Term::Phrase(phrase) => quote_fts(phrase),
+        Term::Raw(expression) => format!("({})", expression),
+        _ => String::new(),
+    }
+}
+
+fn compile_filter(filter: &Filter, params: &mut Vec<Value>) -> String {
+    match filter {
+        Filter::Color(color) => {
+            params.push(Value::Text(color.to_string()));
+            "s.color = ?".to_string()
+        }
+        Filter::Machine(machine) => {
+            params.push(Value::Text(machine.clone()));
+            "s.source_machine = ? COLLATE NOCASE".to_string()
+        }
+        Filter::Tag(tag) => {
+            params.push(Value::Text(tag.clone()));
+            "EXISTS (SELECT 1 FROM sticky_tags st JOIN tags t ON t.id = st.tag_id
+                     WHERE st.sticky_uuid = s.uuid AND t.name = ?)"
+                .to_string()
+        }
+        Filter::Modified(date) => compile_date("s.modified_at", date, params),
+        Filter::Created(date) => compile_date("s.created_at", date, params),
+        Filter::Has(HasFilter::Attachment) => {
+            "EXISTS (SELECT 1 FROM attachments a WHERE a.sticky_uuid = s.uuid)".to_string()
+        }
+        Filter::Has(HasFilter::Image) => {
+            let extensions = IMAGE_EXTENSIONS
+                .iter()
+                .map(|ext| format!("lower(a.filename) LIKE '%.{}'", ext))
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            format!(
+                "EXISTS (SELECT 1 FROM attachments a WHERE a.sticky_uuid = s.uuid \
+                 AND (a.mime_type LIKE 'image/%' OR (a.mime_type IS NULL AND ({}))))",
+                extensions
+            )
+        }
+        Filter::Is(IsFilter::Floating) => "s.is_floating = 1".to_string(),
+    }
+}
+
+fn compile_date(column: &str, date: &DateFilter, params: &mut Vec<Value>) -> String {
+    let day_end = date.day_start + SECONDS_PER_DAY;
+
+    match date.comparison {
+        Comparison::Before => {
+            params.push(Value::Integer(date.day_start));
+            format!("{} < ?", column)
+        }
+        Comparison::OnOrBefore => {
+            params.push(Value::Integer(day_end));
+            format!("{} < ?", column)
+        }
+        Comparison::After => {
+            params.push(Value::Integer(day_end));
+            format!("{} >= ?", column)
+        }
+        Comparison::OnOrAfter => {
+            params.push(Value::Integer(date.day_start));
+            format!("{} >= ?", column)
+        }
+        Comparison::On => {
+            params.push(Value::Integer(date.day_start));
+            params.push(Value::Integer(day_end));
+            format!("({} >= ? AND {} < ?)", column, column)
+        }
+    }
+}
@@ -1,5 +1,5 @@
 // ABOUTME: RTF text extraction for search indexing
-// ABOUTME: Simple regex-based stripper to extract plain text from RTF data
+// ABOUTME: Simple regex-based stripper to extract plain text and attachment references from RTF data
 
 use regex::Regex;
 use std::sync::OnceLock;
@@ -7,6 +7,7 @@ use std::sync::OnceLock;
 // Compile regexes once at first use for performance
 static CONTROL_RE: OnceLock<Regex> = OnceLock::new();
 static SPACE_RE: OnceLock<Regex> = OnceLock::new();
+static ATTACHMENT_RE: OnceLock<Regex> = OnceLock::new();
 
 pub fn extract_text(rtf: &str) -> String {
     // Remove RTF control sequences like \rtf1, \ansi, etc.
@@ -29,3 +30,21 @@ pub fn extract_text_from_bytes(rtf_data: &[u8]) -> String {
         Err(_) => String::new(),
     }
 }
+
+/// Names of the attachments an RTFD document embeds, in document order and
+/// without duplicates. Cocoa writes each one as `{{\NeXTGraphic name \width..}`
+/// (images) or `\attachment name` (other files).
+pub fn attachment_references(rtf: &str) -> Vec<String> {
+    let attachment_re = ATTACHMENT_RE.get_or_init(|| {
+        Regex::new(r"\\(?:NeXTGraphic|attachment)\s+([^\\{}]+?)\s*(?:\\|\}|$)").unwrap()
+    });
+
+    let mut names: Vec<String> = Vec::new();
+    for captures in attachment_re.captures_iter(rtf) {
+        let name = captures[1].trim().to_string();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
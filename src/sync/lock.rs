@@ -0,0 +1,117 @@
+// ABOUTME: Inter-process lock that keeps two syncs from writing at the same time
+// ABOUTME: Combines an advisory lock file next to the database with a lease row inside it
+
+use crate::database::{Database, SyncLease};
+use crate::{Result, StickyError};
+use std::fs::{File, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// A lease older than this is assumed to belong to a sync that died without
+/// releasing it (a crash, or a laptop that went to sleep mid-sync)
+pub const LEASE_TIMEOUT_SECS: i64 = 15 * 60;
+
+/// Held for the duration of a sync; released when dropped.
+///
+/// The lock file stops runs on the same machine from overlapping. File locks
+/// don't travel through iCloud Drive or Dropbox, so the lease row in the
+/// database covers other machines sharing it.
+pub struct SyncLock<'a> {
+    db: &'a Database,
+    _file: File,
+    machine: String,
+    pid: u32,
+}
+
+impl<'a> SyncLock<'a> {
+    /// Take the lock for this installation's `machine` ID; `hostname` only
+    /// names the holder to anyone the lock blocks
+    pub fn acquire(db: &'a Database, machine: &str, hostname: &str) -> Result<Self> {
+        let pid = std::process::id();
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_path(db))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                file.read_to_string(&mut holder)?;
+                return Err(locked_by(&holder));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{} {}", hostname, pid)?;
+        file.flush()?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let lease = SyncLease {
+            machine: machine.to_string(),
+            hostname: hostname.to_string(),
+            pid,
+            acquired_at: now,
+        };
+        db.acquire_lease(&lease, |current| {
+            // A lease from this machine is stale once its process has exited;
+            // holding the lock file alone doesn't show that, as file locks
+            // don't reach copies of the database synced to other machines
+            (current.machine == machine && !process_is_running(current.pid))
+                || now - current.acquired_at > LEASE_TIMEOUT_SECS
+        })?;
+
+        Ok(Self {
+            db,
+            _file: file,
+            machine: machine.to_string(),
+            pid,
+        })
+    }
+}
+
+impl Drop for SyncLock<'_> {
+    fn drop(&mut self) {
+        // The file lock is released when the file closes; a lease that can't
+        // be removed here expires after LEASE_TIMEOUT_SECS
+        let _ = self.db.release_lease(&self.machine, self.pid);
+    }
+}
+
+/// `stickies.db` is locked through `stickies.db.lock`
+fn lock_path(db: &Database) -> PathBuf {
+    let mut path = db.path().as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Whether `pid` is a live process on this machine (`kill -0` sends no signal)
+pub(crate) fn process_is_running(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map_or(true, |status| status.success())
+}
+
+/// The lock file holds `<hostname> <pid>` of the sync that has it
+fn locked_by(holder: &str) -> StickyError {
+    let (hostname, pid) = holder
+        .trim()
+        .rsplit_once(' ')
+        .unwrap_or((holder.trim(), ""));
+    StickyError::Locked {
+        hostname: if hostname.is_empty() {
+            "this machine".to_string()
+        } else {
+            hostname.to_string()
+        },
+        pid: pid.parse().unwrap_or(0),
+    }
+}
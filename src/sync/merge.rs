@@ -0,0 +1,219 @@
+// ABOUTME: Merges another sticky-situation database into this one
+// ABOUTME: Applies the SyncEngine rules with the other database in the filesystem's place
+
+use super::lock::SyncLock;
+use super::runner::{self, SyncOptions, SyncReport};
+use super::{Conflict, SyncAction, SyncEngine, Version};
+use crate::database::{AttachmentInfo, Database, StickyRecord, StoredVersion};
+use crate::filesystem::StickyMetadata;
+use crate::version_vector::VersionVector;
+use crate::{Result, StickyError};
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Merge the stickies from the database at `other_path` into `db`. The
+/// other database wins where its copy includes `db`'s edits (or, without
+/// version vectors or for concurrent edits, is newer), as the filesystem
+/// would in a sync. Manual tags from both are kept. Stickies only in `db`
+/// are left alone. Nothing is deleted: there are no tombstones, so a sticky
+/// deleted from `db` that `other_path` still has is added back.
+///
+/// The report's actions read from `db`'s side: `NewOnFilesystem` was added,
+/// `UpdateDatabase` replaced, `UpdateLayout` had its window state updated and
+/// everything else is `NoChange`.
+pub fn merge_database(
+    db: &Database,
+    other_path: &Path,
+    options: &SyncOptions,
+) -> Result<SyncReport> {
+    let _lock = if options.dry_run {
+        None
+    } else {
+        Some(SyncLock::acquire(
+            db,
+            &options.machine_id,
+            &options.hostname,
+        )?)
+    };
+
+    let snapshot = Snapshot::take(other_path)?;
+    let other = Database::create(&snapshot.path)?;
+
+    let ours = db.get_modified_times()?;
+    let theirs = other.get_modified_times()?;
+
+    let their_uuids: Vec<String> = theirs.keys().cloned().collect();
+    let actions =
+        SyncEngine::categorize_versions(&their_uuids, &versions(&ours), &versions(&theirs));
+    let (actions, concurrent) =
+        SyncEngine::order_by_vectors(actions, &vectors(&ours), &vectors(&theirs));
+    let conflicts: Vec<Conflict> = concurrent
+        .into_iter()
+        .map(|uuid| {
+            let detail = if actions.contains(&SyncAction::UpdateDatabase(uuid.clone())) {
+                format!(
+                    "edited in both databases; kept the newer copy from {}",
+                    other_path.display()
+                )
+            } else {
+                "edited in both databases; kept this database's newer copy".to_string()
+            };
+            Conflict { uuid, detail }
+        })
+        .collect();
+    let actions =
+        SyncEngine::detect_layout_changes(actions, &window_states(&ours), &window_states(&theirs));
+    let actions: Vec<SyncAction> = actions
+        .into_iter()
+        .map(|action| match action {
+            SyncAction::NewOnFilesystem(_)
+            | SyncAction::UpdateDatabase(_)
+            | SyncAction::UpdateLayout(_) => action,
+            other => SyncAction::NoChange(other.uuid().to_string()),
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    if options.dry_run {
+        return Ok(SyncReport {
+            actions,
+            warnings,
+            failures: Vec::new(),
+            conflicts,
+        });
+    }
+
+    db.in_transaction(|| {
+        for action in &actions {
+            match action {
+                SyncAction::NewOnFilesystem(uuid) | SyncAction::UpdateDatabase(uuid) => {
+                    let Some(mut sticky) = other.get_sticky(uuid)? else {
+                        continue;
+                    };
+                    // Keeps both histories when a concurrent edit is replaced
+                    if let Some(stored) = ours.get(uuid) {
+                        sticky.version_vector =
+                            sticky.version_vector.merged(&stored.version_vector);
+                    }
+                    let attachments = other.get_attachments(uuid)?;
+                    let mut skipped: Vec<AttachmentInfo> = other
+                        .get_attachment_info(uuid)?
+                        .into_iter()
+                        .filter(|info| !info.stored)
+                        .collect();
+                    if !skipped.is_empty() {
+                        warnings.push(format!(
+                            "{}: {} attachment(s) over the size limit were never stored in {}; \
+                             they stay on the machine that has them",
+                            uuid,
+                            skipped.len(),
+                            other_path.display()
+                        ));
+                    }
+                    // Ours stay recorded too, so the machine holding them keeps them
+                    for info in db.get_attachment_info(uuid)? {
+                        let known = attachments.iter().any(|a| a.filename == info.filename)
+                            || skipped.iter().any(|s| s.filename == info.filename);
+                        if !info.stored && !known {
+                            skipped.push(info);
+                        }
+                    }
+                    let record = StickyRecord {
+                        sticky,
+                        attachments,
+                        skipped,
+                    };
+                    db.insert_batch(&[record])?;
+                }
+                SyncAction::UpdateLayout(uuid) => {
+                    if let Some(stored) = theirs.get(uuid) {
+                        let metadata = StickyMetadata::from_bytes(&stored.plist_metadata)?;
+                        db.update_metadata(uuid, &metadata)?;
+                    }
+                }
+                SyncAction::NoChange(uuid) if conflicts.iter().any(|c| c.uuid == *uuid) => {
+                    // Our copy won; it now includes both histories
+                    if let (Some(stored), Some(their)) = (ours.get(uuid), theirs.get(uuid)) {
+                        let merged = stored.version_vector.merged(&their.version_vector);
+                        db.set_version_vector(uuid, &merged)?;
+                    }
+                }
+                _ => {}
+            }
+
+            for tag in other.get_manual_tags(action.uuid())? {
+                db.add_tag(action.uuid(), &tag)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(SyncReport {
+        actions,
+        warnings,
+        failures: Vec::new(),
+        conflicts,
+    })
+}
+
+fn versions(rows: &HashMap<String, StoredVersion>) -> HashMap<String, Version> {
+    rows.iter()
+        .map(|(uuid, row)| {
+            let version = Version {
+                modified_ns: runner::modified_ns(row.modified_ns, row.modified_at),
+                content_hash: row.content_hash.clone(),
+            };
+            (uuid.clone(), version)
+        })
+        .collect()
+}
+
+fn vectors(rows: &HashMap<String, StoredVersion>) -> HashMap<String, VersionVector> {
+    rows.iter()
+        .map(|(uuid, row)| (uuid.clone(), row.version_vector.clone()))
+        .collect()
+}
+
+fn window_states(
+    rows: &HashMap<String, StoredVersion>,
+) -> HashMap<String, crate::filesystem::WindowState> {
+    rows.iter()
+        .filter_map(|(uuid, row)| {
+            let metadata = StickyMetadata::from_bytes(&row.plist_metadata).ok()?;
+            Some((uuid.clone(), metadata.window_state()))
+        })
+        .collect()
+}
+
+/// A consistent copy of a database, taken without writing to the original
+/// (which may be a backup, or use an older schema), removed when dropped
+struct Snapshot {
+    path: PathBuf,
+}
+
+impl Snapshot {
+    fn take(source: &Path) -> Result<Self> {
+        if !source.is_file() {
+            return Err(StickyError::NotFound(format!(
+                "Database {}",
+                source.display()
+            )));
+        }
+        let path = std::env::temp_dir().join(format!(
+            "sticky-merge-{}-{}.db",
+            std::process::id(),
+            uuid::Uuid::new_v4()
+        ));
+        let conn = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
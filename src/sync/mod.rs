@@ -0,0 +1,306 @@
+// ABOUTME: Sync engine for bidirectional merge between filesystem and database
+// ABOUTME: Detects changes by content hash and orders them by version vector, then timestamp
+
+use crate::version_vector::{Causality, VersionVector};
+use std::collections::HashMap;
+
+pub mod lock;
+pub mod merge;
+pub mod runner;
+pub mod shared;
+pub mod watch;
+
+pub use runner::{SyncFailure, SyncOptions, SyncReport};
+pub use watch::WatchOptions;
+
+#[derive(Debug, PartialEq)]
+pub enum SyncAction {
+    NewOnFilesystem(String),  // UUID needs to be inserted into DB
+    NewInDatabase(String),    // UUID needs to be written to filesystem
+    UpdateFilesystem(String), // DB version is newer
+    UpdateDatabase(String),   // Filesystem version is newer
+    NoChange(String),         // Content (or timestamps) match
+    UpdateLayout(String),     // Content unchanged, window layout moved on filesystem
+    /// Content unchanged, window layout changed in the database (on another
+    /// machine, or by `sticky set`); written to the Stickies state file
+    UpdateFilesystemLayout(String),
+    /// A database row that Stickies.app re-imported under a new UUID; the row
+    /// is moved to the filesystem UUID instead of duplicating the note
+    Rekey {
+        from: String,
+        to: String,
+    },
+}
+
+impl SyncAction {
+    /// The sticky the action applies to; for a re-key, its new UUID
+    pub fn uuid(&self) -> &str {
+        match self {
+            SyncAction::NewOnFilesystem(uuid)
+            | SyncAction::NewInDatabase(uuid)
+            | SyncAction::UpdateFilesystem(uuid)
+            | SyncAction::UpdateDatabase(uuid)
+            | SyncAction::NoChange(uuid)
+            | SyncAction::UpdateLayout(uuid)
+            | SyncAction::UpdateFilesystemLayout(uuid) => uuid,
+            SyncAction::Rekey { to, .. } => to,
+        }
+    }
+}
+
+/// What sync knows about one copy of a sticky
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version {
+    /// Modification time in nanoseconds since the epoch
+    pub modified_ns: i64,
+    /// `RtfdBundle::content_hash`, or empty when not known
+    pub content_hash: String,
+}
+
+/// A sticky edited in two places without either edit seeing the other. The
+/// newer copy is kept, as before version vectors existed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub uuid: String,
+    /// Which copy was kept and which was replaced
+    pub detail: String,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.uuid, self.detail)
+    }
+}
+
+pub struct SyncEngine;
+
+impl SyncEngine {
+    pub fn categorize(
+        fs_uuids: &[String],
+        db_times: &HashMap<String, i64>,
+        fs_times: &HashMap<String, i64>,
+    ) -> Vec<SyncAction> {
+        let mut actions = Vec::new();
+
+        // Check filesystem UUIDs
+        for uuid in fs_uuids {
+            match db_times.get(uuid) {
+                None => {
+                    // New on filesystem
+                    actions.push(SyncAction::NewOnFilesystem(uuid.clone()));
+                }
+                Some(&db_time) => {
+                    let fs_time = fs_times.get(uuid).copied().unwrap_or(0);
+
+                    if fs_time > db_time {
+                        actions.push(SyncAction::UpdateDatabase(uuid.clone()));
+                    } else if db_time > fs_time {
+                        actions.push(SyncAction::UpdateFilesystem(uuid.clone()));
+                    } else {
+                        actions.push(SyncAction::NoChange(uuid.clone()));
+                    }
+                }
+            }
+        }
+
+        // Check for UUIDs only in database
+        for uuid in db_times.keys() {
+            if !fs_uuids.contains(uuid) {
+                actions.push(SyncAction::NewInDatabase(uuid.clone()));
+            }
+        }
+
+        actions
+    }
+
+    /// Like `categorize`, but a sticky only counts as changed when the content
+    /// hashes differ, so a `touch` or a rewrite of identical content is not an
+    /// edit. Timestamps then decide which side wins; on an exact tie the
+    /// filesystem copy, which the user is looking at, is kept. Copies without
+    /// a hash (rows stored before hashes existed) fall back to timestamps.
+    pub fn categorize_versions(
+        fs_uuids: &[String],
+        db_versions: &HashMap<String, Version>,
+        fs_versions: &HashMap<String, Version>,
+    ) -> Vec<SyncAction> {
+        let mut actions = Vec::new();
+
+        for uuid in fs_uuids {
+            let (Some(db), Some(fs)) = (db_versions.get(uuid), fs_versions.get(uuid)) else {
+                actions.push(SyncAction::NewOnFilesystem(uuid.clone()));
+                continue;
+            };
+
+            let hashes_known = !db.content_hash.is_empty() && !fs.content_hash.is_empty();
+            let action = if hashes_known && db.content_hash == fs.content_hash {
+                SyncAction::NoChange(uuid.clone())
+            } else if hashes_known {
+                if db.modified_ns > fs.modified_ns {
+                    SyncAction::UpdateFilesystem(uuid.clone())
+                } else {
+                    SyncAction::UpdateDatabase(uuid.clone())
+                }
+            } else if fs.modified_ns > db.modified_ns {
+                SyncAction::UpdateDatabase(uuid.clone())
+            } else if db.modified_ns > fs.modified_ns {
+                SyncAction::UpdateFilesystem(uuid.clone())
+            } else {
+                SyncAction::NoChange(uuid.clone())
+            };
+            actions.push(action);
+        }
+
+        for uuid in db_versions.keys() {
+            if !fs_uuids.contains(uuid) {
+                actions.push(SyncAction::NewInDatabase(uuid.clone()));
+            }
+        }
+
+        actions
+    }
+
+    /// Pair notes that only exist in the database with new filesystem notes of
+    /// identical content, turning each pair into a single `Rekey`. Only rows in
+    /// `candidates` (normally those created on this machine) are considered,
+    /// and each is matched at most once.
+    pub fn reconcile_rekeys(
+        actions: Vec<SyncAction>,
+        candidates: &HashMap<String, Version>,
+        fs_versions: &HashMap<String, Version>,
+    ) -> Vec<SyncAction> {
+        let mut orphans: Vec<&String> = actions
+            .iter()
+            .filter_map(|action| match action {
+                SyncAction::NewInDatabase(uuid) if candidates.contains_key(uuid) => Some(uuid),
+                _ => None,
+            })
+            .collect();
+        // Deterministic pairing when several rows share the same content
+        orphans.sort();
+
+        let mut rekeyed: HashMap<String, String> = HashMap::new();
+        for action in &actions {
+            let SyncAction::NewOnFilesystem(to) = action else {
+                continue;
+            };
+            let Some(fs_hash) = fs_versions.get(to).map(|v| &v.content_hash) else {
+                continue;
+            };
+            if fs_hash.is_empty() {
+                continue;
+            }
+            let matched = orphans.iter().position(|from| {
+                !rekeyed.contains_key(*from) && &candidates[*from].content_hash == fs_hash
+            });
+            if let Some(index) = matched {
+                rekeyed.insert(orphans[index].clone(), to.clone());
+            }
+        }
+
+        let targets: HashMap<&String, &String> = rekeyed.iter().map(|(f, t)| (t, f)).collect();
+        actions
+            .into_iter()
+            .filter_map(|action| match action {
+                SyncAction::NewInDatabase(uuid) if rekeyed.contains_key(&uuid) => None,
+                SyncAction::NewOnFilesystem(uuid) => match targets.get(&uuid) {
+                    Some(from) => Some(SyncAction::Rekey {
+                        from: (*from).clone(),
+                        to: uuid,
+                    }),
+                    None => Some(SyncAction::NewOnFilesystem(uuid)),
+                },
+                other => Some(other),
+            })
+            .collect()
+    }
+
+    /// Re-order stickies whose content differs by their version vectors, so a
+    /// copy that already includes the other side's edits wins whatever the
+    /// clocks say. Stickies without a vector on either side (rows from older
+    /// versions) keep their timestamp order, as do concurrent edits, whose
+    /// UUIDs are also returned.
+    pub fn order_by_vectors(
+        actions: Vec<SyncAction>,
+        db_vectors: &HashMap<String, VersionVector>,
+        fs_vectors: &HashMap<String, VersionVector>,
+    ) -> (Vec<SyncAction>, Vec<String>) {
+        let mut conflicts = Vec::new();
+        let actions = actions
+            .into_iter()
+            .map(|action| {
+                let uuid = match &action {
+                    SyncAction::UpdateDatabase(uuid) | SyncAction::UpdateFilesystem(uuid) => {
+                        uuid.clone()
+                    }
+                    _ => return action,
+                };
+                let (Some(db), Some(fs)) = (db_vectors.get(&uuid), fs_vectors.get(&uuid)) else {
+                    return action;
+                };
+                if db.is_empty() || fs.is_empty() {
+                    return action;
+                }
+                match fs.compare(db) {
+                    Causality::After => SyncAction::UpdateDatabase(uuid),
+                    Causality::Before => SyncAction::UpdateFilesystem(uuid),
+                    Causality::Equal => action,
+                    Causality::Concurrent => {
+                        conflicts.push(uuid);
+                        action
+                    }
+                }
+            })
+            .collect();
+        (actions, conflicts)
+    }
+
+    /// Turn `NoChange` into `UpdateLayout` for stickies whose content is the
+    /// same but whose window layout on the filesystem differs from the one
+    /// stored in the database, so moves and resizes are synced without
+    /// treating the note as edited
+    pub fn detect_layout_changes<T: PartialEq>(
+        actions: Vec<SyncAction>,
+        db_layouts: &HashMap<String, T>,
+        fs_layouts: &HashMap<String, T>,
+    ) -> Vec<SyncAction> {
+        actions
+            .into_iter()
+            .map(|action| match action {
+                SyncAction::NoChange(uuid) => match fs_layouts.get(&uuid) {
+                    Some(fs_layout) if db_layouts.get(&uuid) != Some(fs_layout) => {
+                        SyncAction::UpdateLayout(uuid)
+                    }
+                    _ => SyncAction::NoChange(uuid),
+                },
+                other => other,
+            })
+            .collect()
+    }
+    /// Decide which way each `UpdateLayout` goes. A filesystem still holding
+    /// the layout this machine last synced (`synced_layouts`) is stale, so
+    /// the database's layout is written to it instead. Otherwise the
+    /// filesystem's layout wins, as it does when nothing was synced before
+    /// or the database has no layout to give.
+    pub fn direct_layout_changes<T: PartialEq>(
+        actions: Vec<SyncAction>,
+        db_layouts: &HashMap<String, T>,
+        fs_layouts: &HashMap<String, T>,
+        synced_layouts: &HashMap<String, T>,
+    ) -> Vec<SyncAction> {
+        actions
+            .into_iter()
+            .map(|action| match action {
+                SyncAction::UpdateLayout(uuid) => match synced_layouts.get(&uuid) {
+                    Some(synced)
+                        if fs_layouts.get(&uuid) == Some(synced)
+                            && db_layouts.contains_key(&uuid) =>
+                    {
+                        SyncAction::UpdateFilesystemLayout(uuid)
+                    }
+                    _ => SyncAction::UpdateLayout(uuid),
+                },
+                other => other,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,802 @@
+// ABOUTME: Sync runner applying SyncEngine decisions to the filesystem and database
+// ABOUTME: Imports Stickies bundles and plist state into the DB and writes DB changes back
+
+use super::lock::SyncLock;
+use super::shared;
+use super::{Conflict, SyncAction, SyncEngine, Version};
+use crate::config::DEFAULT_MAX_ATTACHMENT_BYTES;
+use crate::database::{AttachmentInfo, Database, ScanEntry, Sticky, StickyRecord, SyncedVersion};
+use crate::filesystem::{
+    self, app, plist,
+    rtfd::{Attachment, RtfdBundle},
+    StickyColor, StickyMetadata,
+};
+use crate::version_vector::VersionVector;
+use crate::{rtf, Result, StickyError};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub struct SyncOptions {
+    /// Work out what would change without touching the filesystem or database
+    pub dry_run: bool,
+    /// Names the lock holder in errors, and keyed this machine's sync state
+    /// (and shared change log) before machine IDs existed
+    pub hostname: String,
+    /// Stable ID of this installation (see `Config::ensure_machine_id`),
+    /// counted in version vectors and keying this machine's sync state
+    pub machine_id: String,
+    /// Attachments larger than this are recorded but their content is left
+    /// out of the database; 0 disables the limit
+    pub max_attachment_bytes: u64,
+    /// Folder of per-machine change logs (shared mode); the database is then
+    /// private to this machine
+    pub shared_dir: Option<PathBuf>,
+    /// File that conflicting edits are appended to, if any
+    pub conflict_log: Option<PathBuf>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        let hostname = hostname::get()
+            .unwrap_or_else(|_| "unknown".into())
+            .to_string_lossy()
+            .to_string();
+
+        Self {
+            dry_run: false,
+            machine_id: hostname.clone(),
+            hostname,
+            max_attachment_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
+            shared_dir: None,
+            conflict_log: None,
+        }
+    }
+}
+
+/// A sticky that couldn't be synced; the rest of the sync went ahead
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncFailure {
+    pub uuid: String,
+    pub error: String,
+}
+
+impl SyncFailure {
+    fn new(uuid: &str, error: StickyError) -> Self {
+        Self {
+            uuid: uuid.to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub actions: Vec<SyncAction>,
+    /// Problems worth telling the user about that didn't stop the sync
+    pub warnings: Vec<String>,
+    /// Stickies skipped because their bundle couldn't be read or written.
+    /// They are quarantined and retried by the next sync.
+    pub failures: Vec<SyncFailure>,
+    /// Stickies edited in two places since they last synced
+    pub conflicts: Vec<Conflict>,
+}
+
+impl SyncReport {
+    /// Number of stickies that were (or, in a dry run, would be) changed
+    pub fn changed(&self) -> usize {
+        self.actions
+            .iter()
+            .filter(|a| !matches!(a, SyncAction::NoChange(_)))
+            .count()
+    }
+}
+
+/// Run a bidirectional sync between a Stickies directory and the database
+pub fn run(db: &Database, stickies_path: &Path, options: &SyncOptions) -> Result<SyncReport> {
+    if options.dry_run {
+        // A dry run doesn't wait for a running sync. Changes from the shared
+        // logs are merged so the plan includes them, then rolled back.
+        return db.rolled_back(|| sync(db, stickies_path, options));
+    }
+
+    let _lock = SyncLock::acquire(db, &options.machine_id, &options.hostname)?;
+    sync(db, stickies_path, options)
+}
+
+fn sync(db: &Database, stickies_path: &Path, options: &SyncOptions) -> Result<SyncReport> {
+    let machine = &options.machine_id;
+    let mut failures = Vec::new();
+    let mut warnings = Vec::new();
+    let mut conflicts = Vec::new();
+    if !options.dry_run {
+        // State saved before machine IDs existed is keyed by hostname
+        if options.hostname != *machine {
+            db.rename_machine(&options.hostname, machine)?;
+        }
+
+        // Put back bundles a crashed write left moved aside, then finish
+        // writing bundles from a sync that committed but was interrupted
+        // before they were all on disk
+        RtfdBundle::recover_interrupted_writes(stickies_path)?;
+        write_pending_exports(db, stickies_path, machine, &mut failures)?;
+    }
+
+    // Take in what other machines wrote to the shared change logs
+    if let Some(shared_dir) = &options.shared_dir {
+        let mut merge = || {
+            let own_logs = [machine.as_str(), options.hostname.as_str()];
+            shared::merge(db, shared_dir, &own_logs, &mut conflicts)
+        };
+        warnings.extend(if options.dry_run {
+            merge()?
+        } else {
+            db.in_transaction(merge)?
+        });
+    }
+
+    // Read filesystem state from .SavedStickiesState (the actual file Stickies.app uses)
+    let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+    let metadata_map = plist::read_stickies_state(&plist_path)?;
+
+    // Read database state in one query, without loading any content
+    let mut db_versions = HashMap::new();
+    let mut db_layouts = HashMap::new();
+    let mut db_vectors = HashMap::new();
+    // Rows written by this machine, which may have been re-keyed by Stickies.app
+    let mut local_versions = HashMap::new();
+
+    for (uuid, stored) in db.get_modified_times()? {
+        if let Ok(metadata) = StickyMetadata::from_bytes(&stored.plist_metadata) {
+            db_layouts.insert(uuid.clone(), metadata.window_state());
+        }
+        let content_hash = if stored.content_hash.is_empty() {
+            legacy_content_hash(db, &uuid, options.dry_run)?
+        } else {
+            stored.content_hash
+        };
+        let version = Version {
+            modified_ns: modified_ns(stored.modified_ns, stored.modified_at),
+            content_hash,
+        };
+        if stored.source_machine == *machine {
+            local_versions.insert(uuid.clone(), version.clone());
+        }
+        db_vectors.insert(uuid.clone(), stored.version_vector);
+        db_versions.insert(uuid, version);
+    }
+
+    // Hashes of bundles read by earlier syncs on this machine, so a bundle
+    // whose mtime moved without its content changing is only read once
+    let scan_cache = db.get_scan_cache(machine)?;
+    let mut next_scan_cache = HashMap::new();
+
+    let mut fs_uuids = Vec::new();
+    let mut fs_mtimes = HashMap::new();
+    let mut known_hashes = HashMap::new();
+
+    for uuid in metadata_map.keys() {
+        let rtfd_path = filesystem::bundle_path(stickies_path, uuid);
+        if rtfd_path.exists() {
+            let modified_ns = match RtfdBundle::modified_time_ns(&rtfd_path) {
+                Ok(modified_ns) => modified_ns,
+                Err(e) => {
+                    failures.push(SyncFailure::new(uuid, e));
+                    continue;
+                }
+            };
+            fs_uuids.push(uuid.clone());
+            fs_mtimes.insert(uuid.clone(), modified_ns);
+
+            // An untouched bundle is assumed to still match the database (or
+            // the scan cache), so only bundles whose mtime moved are read
+            let cached = scan_cache
+                .get(uuid)
+                .filter(|entry| entry.modified_ns == modified_ns);
+            match (db_versions.get(uuid), cached) {
+                (Some(db), _) if db.modified_ns == modified_ns => {
+                    known_hashes.insert(uuid.clone(), db.content_hash.clone());
+                }
+                (_, Some(entry)) => {
+                    known_hashes.insert(uuid.clone(), entry.content_hash.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Read and hash the rest in parallel; they are kept for importing below
+    let results: Vec<_> = fs_uuids
+        .par_iter()
+        .filter(|uuid| !known_hashes.contains_key(*uuid))
+        .map(|uuid| {
+            let bundle = RtfdBundle::read(&filesystem::bundle_path(stickies_path, uuid));
+            (uuid.clone(), bundle)
+        })
+        .collect();
+    let mut read_bundles = HashMap::new();
+    for (uuid, result) in results {
+        match result {
+            Ok(bundle) => {
+                known_hashes.insert(uuid.clone(), bundle.content_hash());
+                read_bundles.insert(uuid, bundle);
+            }
+            Err(e) => failures.push(SyncFailure::new(&uuid, e)),
+        }
+    }
+
+    // Leave unreadable stickies out entirely, so the database copy isn't
+    // written over a bundle that may still hold the only copy of a note
+    let failed: HashSet<String> = failures.iter().map(|f| f.uuid.clone()).collect();
+    fs_uuids.retain(|uuid| !failed.contains(uuid));
+    fs_mtimes.retain(|uuid, _| !failed.contains(uuid));
+    db_versions.retain(|uuid, _| !failed.contains(uuid));
+    db_layouts.retain(|uuid, _| !failed.contains(uuid));
+    local_versions.retain(|uuid, _| !failed.contains(uuid));
+
+    let mut fs_versions = HashMap::new();
+    for (uuid, modified_ns) in fs_mtimes {
+        let content_hash = known_hashes.remove(&uuid).unwrap_or_default();
+        next_scan_cache.insert(
+            uuid.clone(),
+            ScanEntry {
+                modified_ns,
+                content_hash: content_hash.clone(),
+            },
+        );
+        fs_versions.insert(
+            uuid,
+            Version {
+                modified_ns,
+                content_hash,
+            },
+        );
+    }
+
+    let fs_layouts: HashMap<_, _> = metadata_map
+        .iter()
+        .map(|(uuid, metadata)| (uuid.clone(), metadata.window_state()))
+        .collect();
+
+    // Each bundle descends from the version this machine last synced, plus
+    // an edit of its own if its content has changed since
+    let synced = db.get_synced_versions(machine)?;
+    let mut fs_vectors = HashMap::new();
+    for (uuid, fs) in &fs_versions {
+        let Some(base) = synced.get(uuid) else {
+            continue;
+        };
+        let mut vector = base.version_vector.clone();
+        if fs.content_hash != base.content_hash {
+            vector.increment(machine);
+        }
+        fs_vectors.insert(uuid.clone(), vector);
+    }
+
+    let actions = SyncEngine::categorize_versions(&fs_uuids, &db_versions, &fs_versions);
+    let (actions, concurrent) = SyncEngine::order_by_vectors(actions, &db_vectors, &fs_vectors);
+    for uuid in concurrent {
+        let kept_local = actions.contains(&SyncAction::UpdateDatabase(uuid.clone()));
+        let detail = if kept_local {
+            "edited here and on another machine; kept this machine's newer copy"
+        } else {
+            "edited here and on another machine; kept the newer copy from the database"
+        };
+        conflicts.push(Conflict {
+            uuid,
+            detail: detail.to_string(),
+        });
+    }
+    // Window states as this machine's state file held them after its last sync
+    let synced_layouts: HashMap<_, _> = db
+        .get_synced_layouts(machine)?
+        .into_iter()
+        .filter_map(|(uuid, bytes)| {
+            let metadata = StickyMetadata::from_bytes(&bytes).ok()?;
+            Some((uuid, metadata.window_state()))
+        })
+        .collect();
+
+    let actions = SyncEngine::detect_layout_changes(actions, &db_layouts, &fs_layouts);
+    let actions =
+        SyncEngine::direct_layout_changes(actions, &db_layouts, &fs_layouts, &synced_layouts);
+    let mut actions = SyncEngine::reconcile_rekeys(actions, &local_versions, &fs_versions);
+
+    if options.dry_run {
+        return Ok(SyncReport {
+            actions,
+            warnings,
+            failures,
+            conflicts,
+        });
+    }
+
+    // Imports are parsed in parallel, then written in one transaction
+    let imports: Vec<_> = actions
+        .iter()
+        .filter_map(|action| match action {
+            SyncAction::NewOnFilesystem(uuid) | SyncAction::UpdateDatabase(uuid) => {
+                Some((uuid, read_bundles.remove(uuid)))
+            }
+            _ => None,
+        })
+        .collect();
+    let prepared: Vec<_> = imports
+        .into_par_iter()
+        .map(|(uuid, bundle)| {
+            let prepared = (|| {
+                let metadata = metadata_map
+                    .get(uuid)
+                    .ok_or_else(|| StickyError::NotFound(format!("Plist entry for {}", uuid)))?;
+                let bundle = match bundle {
+                    Some(bundle) => bundle,
+                    None => RtfdBundle::read(&filesystem::bundle_path(stickies_path, uuid))?,
+                };
+                let modified_ns = fs_versions.get(uuid).map_or(0, |v| v.modified_ns);
+                let version_vector = import_vector(
+                    db_vectors.get(uuid.as_str()),
+                    fs_vectors.get(uuid.as_str()),
+                    machine,
+                );
+                let local_only = db
+                    .get_attachment_info(uuid)?
+                    .into_iter()
+                    .filter(|info| !info.stored)
+                    .collect();
+                prepare_import(
+                    uuid,
+                    bundle,
+                    metadata,
+                    modified_ns,
+                    version_vector,
+                    local_only,
+                    options,
+                )
+            })();
+            (uuid, prepared)
+        })
+        .collect();
+    let mut records = Vec::with_capacity(prepared.len());
+    for (uuid, result) in prepared {
+        match result {
+            Ok((record, import_warnings)) => {
+                records.push(record);
+                warnings.extend(import_warnings);
+            }
+            Err(e) => failures.push(SyncFailure::new(uuid, e)),
+        }
+    }
+
+    // Every database change commits together or not at all. Bundles to write
+    // are journaled in the same transaction and only written once it commits.
+    db.in_transaction(|| {
+        db.insert_batch(&records)?;
+
+        // What this machine's bundles hold once the sync is done; exports are
+        // recorded as they are written
+        let mut synced_now: Vec<(String, SyncedVersion)> = records
+            .iter()
+            .map(|record| {
+                let version = SyncedVersion {
+                    content_hash: record.sticky.content_hash.clone(),
+                    version_vector: record.sticky.version_vector.clone(),
+                };
+                (record.sticky.uuid.clone(), version)
+            })
+            .collect();
+        // Stickies whose plist entry now matches the database
+        let mut layouts_now: Vec<&String> = records.iter().map(|r| &r.sticky.uuid).collect();
+
+        let mut exports = Vec::new();
+        for action in &actions {
+            match action {
+                // Written above
+                SyncAction::NewOnFilesystem(_) | SyncAction::UpdateDatabase(_) => {}
+
+                SyncAction::NewInDatabase(uuid) | SyncAction::UpdateFilesystem(uuid) => {
+                    ensure_metadata(db, uuid)?;
+                    exports.push(uuid.clone());
+                }
+
+                SyncAction::Rekey { from, to } => {
+                    db.rekey_sticky(from, to)?;
+                    if let Some(metadata) = metadata_map.get(to) {
+                        db.update_metadata(to, metadata)?;
+                    }
+                    synced_now.extend(unchanged_version(to, db_vectors.get(from), &fs_versions));
+                    layouts_now.push(to);
+                }
+
+                SyncAction::UpdateLayout(uuid) => {
+                    if let Some(metadata) = metadata_map.get(uuid) {
+                        db.update_metadata(uuid, metadata)?;
+                    }
+                    synced_now.extend(unchanged_version(uuid, db_vectors.get(uuid), &fs_versions));
+                    layouts_now.push(uuid);
+                }
+
+                // Written to the state file once this commits
+                SyncAction::UpdateFilesystemLayout(uuid) => {
+                    synced_now.extend(unchanged_version(uuid, db_vectors.get(uuid), &fs_versions));
+                }
+
+                SyncAction::NoChange(uuid) => {
+                    synced_now.extend(unchanged_version(uuid, db_vectors.get(uuid), &fs_versions));
+                    layouts_now.push(uuid);
+                }
+            }
+        }
+        db.journal_exports(machine, &exports)?;
+
+        // Skip the writes when nothing moved, so an idle sync leaves the database alone
+        synced_now.retain(|(uuid, version)| synced.get(uuid) != Some(version));
+        db.save_synced_versions(machine, &synced_now)?;
+        let mut changed_layouts = Vec::new();
+        for uuid in layouts_now {
+            let Some(metadata) = metadata_map.get(uuid) else {
+                continue;
+            };
+            if synced_layouts.get(uuid) != Some(&metadata.window_state()) {
+                changed_layouts.push((uuid.clone(), metadata.to_bytes()?));
+            }
+        }
+        db.save_synced_layouts(machine, &changed_layouts)?;
+        if next_scan_cache != scan_cache {
+            db.save_scan_cache(machine, &next_scan_cache)?;
+        }
+        Ok(())
+    })?;
+
+    write_pending_exports(db, stickies_path, machine, &mut failures)?;
+    let layout_exports: Vec<&str> = actions
+        .iter()
+        .filter_map(|action| match action {
+            SyncAction::UpdateFilesystemLayout(uuid) => Some(uuid.as_str()),
+            _ => None,
+        })
+        .collect();
+    write_layouts(db, stickies_path, machine, &layout_exports)?;
+
+    // A journaled export that fails is retried straight away by this run
+    failures.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    failures.dedup_by(|a, b| a.uuid == b.uuid);
+    let quarantined: Vec<_> = failures
+        .iter()
+        .map(|f| (f.uuid.clone(), f.error.clone()))
+        .collect();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    db.save_quarantine(machine, &quarantined, now)?;
+
+    if let Some(shared_dir) = &options.shared_dir {
+        shared::publish(db, shared_dir, machine)?;
+    }
+
+    // Report only what actually happened to each sticky
+    let failed: HashSet<&str> = failures.iter().map(|f| f.uuid.as_str()).collect();
+    actions.retain(|action| !failed.contains(action.uuid()));
+    conflicts.retain(|conflict| !failed.contains(conflict.uuid.as_str()));
+
+    if let Some(path) = &options.conflict_log {
+        if let Err(e) = log_conflicts(path, &conflicts, now) {
+            warnings.push(format!(
+                "Couldn't write to conflict log {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
+    Ok(SyncReport {
+        actions,
+        warnings,
+        failures,
+        conflicts,
+    })
+}
+
+/// The vector of a bundle being imported: everything it was based on, or
+/// for a bundle with no synced base, the database copy plus a new edit here
+fn import_vector(
+    db: Option<&VersionVector>,
+    fs: Option<&VersionVector>,
+    machine: &str,
+) -> VersionVector {
+    let db = db.cloned().unwrap_or_default();
+    match fs {
+        Some(fs) => fs.merged(&db),
+        None => {
+            let mut vector = db;
+            vector.increment(machine);
+            vector
+        }
+    }
+}
+
+/// A bundle whose content the sync left as it was, now matching the database
+fn unchanged_version(
+    uuid: &str,
+    db_vector: Option<&VersionVector>,
+    fs_versions: &HashMap<String, Version>,
+) -> Option<(String, SyncedVersion)> {
+    let content_hash = fs_versions.get(uuid)?.content_hash.clone();
+    if content_hash.is_empty() {
+        return None;
+    }
+    let version = SyncedVersion {
+        content_hash,
+        version_vector: db_vector.cloned().unwrap_or_default(),
+    };
+    Some((uuid.to_string(), version))
+}
+
+/// Append conflicts to the log as `<unix time> <uuid>: <detail>` lines
+fn log_conflicts(path: &Path, conflicts: &[Conflict], now: i64) -> std::io::Result<()> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut log = File::options().create(true).append(true).open(path)?;
+    for conflict in conflicts {
+        writeln!(log, "{} {}", now, conflict)?;
+    }
+    Ok(())
+}
+
+/// Write the bundles and plist entries journaled for this machine, then clear
+/// the journal. Writing a bundle is idempotent, so an interrupted run can
+/// simply be repeated.
+fn write_pending_exports(
+    db: &Database,
+    stickies_path: &Path,
+    machine: &str,
+    failures: &mut Vec<SyncFailure>,
+) -> Result<()> {
+    let pending = db.pending_exports(machine)?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut state_updates = HashMap::new();
+    let mut synced = Vec::new();
+    let mut layouts = Vec::new();
+    let mut done = Vec::new();
+    // Stickies.app would overwrite the rewritten notes' state when it quits
+    app::while_quit(false, || {
+        for uuid in pending {
+            match export_sticky(db, stickies_path, &uuid) {
+                Ok((metadata, version)) => {
+                    layouts.push((uuid.clone(), metadata.to_bytes()?));
+                    state_updates.insert(uuid.clone(), metadata.raw);
+                    synced.push((uuid.clone(), version));
+                }
+                // Re-keyed or removed since it was journaled
+                Err(StickyError::NotFound(_)) => {}
+                // Left in the journal to retry next time
+                Err(e) => {
+                    failures.push(SyncFailure::new(&uuid, e));
+                    continue;
+                }
+            }
+            done.push(uuid);
+        }
+
+        if !state_updates.is_empty() {
+            let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+            plist::update_stickies_state(&plist_path, &state_updates)?;
+        }
+        Ok(())
+    })?;
+
+    db.save_synced_versions(machine, &synced)?;
+    db.save_synced_layouts(machine, &layouts)?;
+    db.clear_journal(machine, &done)
+}
+
+/// Write window states changed in the database to the Stickies state file,
+/// leaving the bundles alone. Recorded as synced only once written, so an
+/// interrupted run simply does it again.
+fn write_layouts(db: &Database, stickies_path: &Path, machine: &str, uuids: &[&str]) -> Result<()> {
+    if uuids.is_empty() {
+        return Ok(());
+    }
+
+    let mut state_updates = HashMap::new();
+    let mut layouts = Vec::new();
+    for uuid in uuids {
+        let Some(sticky) = db.get_sticky(uuid)? else {
+            continue;
+        };
+        let metadata = StickyMetadata::from_bytes(&sticky.plist_metadata)?;
+        state_updates.insert(uuid.to_string(), metadata.raw);
+        layouts.push((uuid.to_string(), sticky.plist_metadata));
+    }
+
+    let plist_path = stickies_path.join(filesystem::SAVED_STATE_FILE);
+    app::while_quit(false, || {
+        plist::update_stickies_state(&plist_path, &state_updates)
+    })?;
+    db.save_synced_layouts(machine, &layouts)
+}
+
+/// Rows created before metadata was stored (or by `sticky new`) get a default
+/// entry so Stickies.app still shows the note once exported. It is stored, so
+/// the next sync doesn't see the new plist entry as a layout change.
+fn ensure_metadata(db: &Database, uuid: &str) -> Result<()> {
+    let sticky = db
+        .get_sticky(uuid)?
+        .ok_or_else(|| StickyError::NotFound(format!("Sticky with UUID {} not found", uuid)))?;
+    if sticky.plist_metadata.is_empty() {
+        let color: StickyColor = sticky.color.parse().unwrap_or_default();
+        db.update_metadata(uuid, &StickyMetadata::new(color.index()))?;
+    }
+    Ok(())
+}
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// Rows stored before sub-second times existed only have whole seconds
+pub(super) fn modified_ns(modified_ns: i64, modified_at: i64) -> i64 {
+    if modified_ns > 0 {
+        modified_ns
+    } else {
+        modified_at.saturating_mul(NANOS_PER_SECOND)
+    }
+}
+
+/// Hash a row stored before hashes existed from its stored content, so it
+/// isn't treated as changed. The hash is saved so this happens only once.
+fn legacy_content_hash(db: &Database, uuid: &str, dry_run: bool) -> Result<String> {
+    let sticky = db
+        .get_sticky(uuid)?
+        .ok_or_else(|| StickyError::NotFound(format!("Sticky with UUID {} not found", uuid)))?;
+    let content_hash = RtfdBundle {
+        attachments: db.get_attachments(uuid)?,
+        rtf_data: sticky.rtf_data,
+    }
+    .content_hash();
+    if !dry_run {
+        db.set_content_hash(uuid, &content_hash)?;
+    }
+    Ok(content_hash)
+}
+
+/// Build the database record for a sticky from its RTFD bundle and plist
+/// entry, along with warnings about its attachments. `local_only` are the
+/// attachments already recorded without their content; those the bundle
+/// lacks are still recorded, since they live on another machine and are
+/// missing here only because they were never copied.
+fn prepare_import(
+    uuid: &str,
+    bundle: RtfdBundle,
+    metadata: &StickyMetadata,
+    modified_ns: i64,
+    version_vector: VersionVector,
+    local_only: Vec<AttachmentInfo>,
+    options: &SyncOptions,
+) -> Result<(StickyRecord, Vec<String>)> {
+    let mut warnings = Vec::new();
+    let content_hash = bundle.content_hash();
+    let mtime = modified_ns.div_euclid(NANOS_PER_SECOND);
+
+    let mut skipped: Vec<AttachmentInfo> = local_only
+        .into_iter()
+        .filter(|info| {
+            !bundle
+                .attachments
+                .iter()
+                .any(|a| a.filename == info.filename)
+        })
+        .collect();
+
+    let check = bundle.check_attachments();
+    for filename in check.missing {
+        if skipped.iter().any(|info| info.filename == filename) {
+            continue;
+        }
+        warnings.push(format!(
+            "{}: attachment '{}' is referenced but missing",
+            uuid, filename
+        ));
+    }
+    for filename in check.orphaned {
+        warnings.push(format!(
+            "{}: attachment '{}' is not referenced by the note",
+            uuid, filename
+        ));
+    }
+
+    let limit = options.max_attachment_bytes;
+    let (kept, oversized): (Vec<_>, Vec<_>) = bundle
+        .attachments
+        .into_iter()
+        .partition(|a| limit == 0 || a.size() <= limit);
+    for attachment in oversized {
+        warnings.push(format!(
+            "{}: attachment '{}' ({} bytes) is over max_attachment_bytes ({}); \
+             it stays on this machine only",
+            uuid,
+            attachment.filename,
+            attachment.size(),
+            limit
+        ));
+        skipped.push(AttachmentInfo {
+            mime_type: attachment.mime_type().to_string(),
+            size: attachment.size(),
+            filename: attachment.filename,
+            stored: false,
+        });
+    }
+
+    let sticky = Sticky {
+        uuid: uuid.to_string(),
+        content_text: rtf::extract_text_from_bytes(&bundle.rtf_data),
+        rtf_data: bundle.rtf_data,
+        plist_metadata: metadata.to_bytes()?,
+        is_floating: metadata.is_floating,
+        color: metadata.color_name(),
+        modified_at: mtime,
+        created_at: mtime,
+        source_machine: options.machine_id.clone(),
+        modified_ns,
+        content_hash,
+        version_vector,
+    };
+
+    let record = StickyRecord {
+        sticky,
+        attachments: kept,
+        skipped,
+    };
+    Ok((record, warnings))
+}
+
+/// Write a sticky from the database to its RTFD bundle, returning the plist
+/// metadata to restore into the Stickies state file and the version written
+fn export_sticky(
+    db: &Database,
+    stickies_path: &Path,
+    uuid: &str,
+) -> Result<(StickyMetadata, SyncedVersion)> {
+    let sticky = db
+        .get_sticky(uuid)?
+        .ok_or_else(|| StickyError::NotFound(format!("Sticky with UUID {} not found", uuid)))?;
+
+    let modified_ns = modified_ns(sticky.modified_ns, sticky.modified_at);
+    let rtfd_path = filesystem::bundle_path(stickies_path, uuid);
+    let mut attachments = db.get_attachments(uuid)?;
+
+    // Attachments too big for the database only exist on the machine that
+    // had them; keep this machine's copy rather than dropping it. Names come
+    // from other machines, so anything that could leave the bundle is ignored.
+    for info in db.get_attachment_info(uuid)? {
+        let safe = RtfdBundle::sanitize_filename(&info.filename).is_ok();
+        let local = rtfd_path.join(&info.filename);
+        if !info.stored && safe && local.is_file() {
+            attachments.push(Attachment {
+                content: std::fs::read(&local)?,
+                filename: info.filename,
+            });
+        }
+    }
+
+    let bundle = RtfdBundle {
+        rtf_data: sticky.rtf_data,
+        attachments,
+    };
+    bundle.write(&rtfd_path)?;
+    // Keep the DB timestamp so the next sync doesn't see a newer filesystem copy
+    RtfdBundle::set_modified_time_ns(&rtfd_path, modified_ns)?;
+
+    let version = SyncedVersion {
+        content_hash: sticky.content_hash,
+        version_vector: sticky.version_vector,
+    };
+    // Stored by `ensure_metadata` before the export was journaled
+    Ok((StickyMetadata::from_bytes(&sticky.plist_metadata)?, version))
+}
@@ -0,0 +1,493 @@
+// ABOUTME: Shared mode, syncing machines through per-machine change logs instead of one database
+// ABOUTME: Appends local changes to changes/<machine>.ndjson and merges the other machines' logs
+
+use super::{runner, Conflict};
+use crate::database::{Database, PublishedVersion, StickyRecord, StoredVersion};
+use crate::filesystem::{rtfd::Attachment, StickyMetadata};
+use crate::version_vector::{Causality, VersionVector};
+use crate::{rtf, Result, StickyError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Per-machine change logs, one JSON entry per line
+pub const CHANGES_DIR: &str = "changes";
+/// Note content referenced by the logs, one file per SHA-256
+pub const BLOBS_DIR: &str = "blobs";
+const LOG_EXTENSION: &str = "ndjson";
+
+/// One published version of a sticky. Content lives in write-once blob files
+/// so log lines stay small and no file is ever rewritten.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    pub uuid: String,
+    /// The UUID this sticky had before Stickies.app re-keyed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaces: Option<String>,
+    pub source_machine: String,
+    pub color: String,
+    pub is_floating: bool,
+    pub modified_at: i64,
+    pub modified_ns: i64,
+    pub created_at: i64,
+    pub content_hash: String,
+    /// Missing from entries written before version vectors existed
+    #[serde(default, skip_serializing_if = "VersionVector::is_empty")]
+    pub version_vector: VersionVector,
+    /// Blob holding the RTF
+    pub rtf: String,
+    /// Blob holding the plist metadata
+    pub plist: String,
+    /// The plist metadata blob this machine had published before, so
+    /// machines can tell a newer window state from a concurrent one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_plist: Option<String>,
+    pub attachments: Vec<ChangeAttachment>,
+    /// Tags added with `sticky tag`; merged into every machine's copy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub manual_tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeAttachment {
+    pub filename: String,
+    pub blob: String,
+}
+
+/// Path of a machine's change log; only that machine ever writes to it
+pub fn log_path(shared_dir: &Path, machine: &str) -> PathBuf {
+    shared_dir
+        .join(CHANGES_DIR)
+        .join(format!("{}.{}", log_name(machine), LOG_EXTENSION))
+}
+
+/// Machine names become file names, so anything but letters, digits, `-`,
+/// `_` and `.` is replaced
+fn log_name(machine: &str) -> String {
+    machine
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Append every sticky whose content or metadata changed since this machine
+/// last published it, returning how many were written
+pub fn publish(db: &Database, shared_dir: &Path, machine: &str) -> Result<usize> {
+    let rows = db.get_modified_times()?;
+    let published = db.get_published()?;
+    let mut manual_tags = db.get_all_manual_tags()?;
+
+    // Sync never deletes, so a published sticky that is gone was re-keyed
+    let mut gone: Vec<(String, String)> = published
+        .iter()
+        .filter(|(uuid, _)| !rows.contains_key(*uuid))
+        .map(|(uuid, version)| (uuid.clone(), version.content_hash.clone()))
+        .collect();
+
+    let mut uuids: Vec<&String> = rows.keys().collect();
+    uuids.sort();
+
+    let mut lines = String::new();
+    let mut versions = Vec::new();
+    for uuid in uuids {
+        let row = &rows[uuid];
+        let tags = manual_tags.remove(uuid).unwrap_or_default();
+        let version = PublishedVersion {
+            content_hash: row.content_hash.clone(),
+            metadata_hash: blob_id(&row.plist_metadata),
+            tags_hash: tags_hash(&tags),
+        };
+        if published.get(uuid) == Some(&version) {
+            continue;
+        }
+
+        let sticky = db
+            .get_sticky(uuid)?
+            .ok_or_else(|| StickyError::NotFound(format!("Sticky with UUID {} not found", uuid)))?;
+        let replaces = if published.contains_key(uuid) {
+            None
+        } else {
+            gone.iter()
+                .position(|(_, hash)| *hash == row.content_hash)
+                .map(|i| gone.remove(i).0)
+        };
+
+        let attachments = db
+            .get_attachments(uuid)?
+            .iter()
+            .map(|a| {
+                Ok(ChangeAttachment {
+                    filename: a.filename.clone(),
+                    blob: write_blob(shared_dir, &a.content)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let entry = ChangeEntry {
+            uuid: uuid.clone(),
+            replaces,
+            source_machine: sticky.source_machine,
+            color: sticky.color,
+            is_floating: sticky.is_floating,
+            modified_at: sticky.modified_at,
+            modified_ns: sticky.modified_ns,
+            created_at: sticky.created_at,
+            content_hash: sticky.content_hash,
+            version_vector: sticky.version_vector,
+            rtf: write_blob(shared_dir, &sticky.rtf_data)?,
+            plist: write_blob(shared_dir, &sticky.plist_metadata)?,
+            previous_plist: published.get(uuid).map(|p| p.metadata_hash.clone()),
+            attachments,
+            manual_tags: tags,
+        };
+
+        let line = serde_json::to_string(&entry).map_err(|e| StickyError::Io(e.into()))?;
+        lines.push_str(&line);
+        lines.push('\n');
+        versions.push((uuid.clone(), version));
+    }
+
+    if !lines.is_empty() {
+        let path = log_path(shared_dir, machine);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut log = File::options().create(true).append(true).open(&path)?;
+        log.write_all(lines.as_bytes())?;
+        log.sync_all()?;
+    }
+
+    // Recorded only once the log is on disk; after a crash in between the
+    // entries are written again, which merging tolerates
+    for (uuid, version) in &versions {
+        db.set_published(uuid, version)?;
+    }
+    for (uuid, _) in &gone {
+        db.remove_published(uuid)?;
+    }
+
+    Ok(versions.len())
+}
+
+/// Apply new entries from every other machine's change log, returning
+/// warnings about entries that couldn't be read. `own_logs` names this
+/// machine's logs, including one written under its hostname before it had a
+/// machine ID. Concurrent edits are added to `conflicts`.
+pub fn merge(
+    db: &Database,
+    shared_dir: &Path,
+    own_logs: &[&str],
+    conflicts: &mut Vec<Conflict>,
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    let changes_dir = shared_dir.join(CHANGES_DIR);
+    if !changes_dir.is_dir() {
+        return Ok(warnings);
+    }
+
+    let own_logs: Vec<PathBuf> = own_logs
+        .iter()
+        .map(|machine| log_path(shared_dir, machine))
+        .collect();
+    let mut logs: Vec<PathBuf> = fs::read_dir(&changes_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == LOG_EXTENSION))
+        .filter(|path| !own_logs.contains(path))
+        .collect();
+    logs.sort();
+
+    let mut local = db.get_modified_times()?;
+    let mut published = db.get_published()?;
+    for path in logs {
+        let log = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let data = fs::read(&path)?;
+
+        // A log shorter than what was merged has been replaced; start over
+        // (entries that are already applied are skipped)
+        let mut offset = db.get_log_offset(&log)? as usize;
+        if offset > data.len() {
+            offset = 0;
+        }
+
+        // Only complete lines; the last one may still be arriving
+        while let Some(end) = data[offset..].iter().position(|&b| b == b'\n') {
+            let line = &data[offset..offset + end];
+            if !line.iter().all(u8::is_ascii_whitespace) {
+                match serde_json::from_slice::<ChangeEntry>(line) {
+                    Ok(entry) => {
+                        if !apply(
+                            db,
+                            shared_dir,
+                            &entry,
+                            &mut local,
+                            &mut published,
+                            conflicts,
+                        )? {
+                            // Its content hasn't synced to this machine yet
+                            break;
+                        }
+                    }
+                    Err(e) => warnings.push(format!(
+                        "{}: skipping unreadable change at byte {}: {}",
+                        log, offset, e
+                    )),
+                }
+            }
+            offset += end + 1;
+        }
+
+        db.set_log_offset(&log, offset as u64)?;
+    }
+
+    Ok(warnings)
+}
+
+/// Apply one entry unless the local copy already includes it. Returns false,
+/// leaving the database alone, when some of its blobs haven't arrived yet.
+fn apply(
+    db: &Database,
+    shared_dir: &Path,
+    entry: &ChangeEntry,
+    local: &mut HashMap<String, StoredVersion>,
+    published: &mut HashMap<String, PublishedVersion>,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<bool> {
+    let Some(rtf_data) = read_blob(shared_dir, &entry.rtf)? else {
+        return Ok(false);
+    };
+    let Some(mut plist_metadata) = read_blob(shared_dir, &entry.plist)? else {
+        return Ok(false);
+    };
+    let mut attachments = Vec::new();
+    for attachment in &entry.attachments {
+        let Some(content) = read_blob(shared_dir, &attachment.blob)? else {
+            return Ok(false);
+        };
+        attachments.push(Attachment {
+            filename: attachment.filename.clone(),
+            content,
+        });
+    }
+
+    if let Some(from) = &entry.replaces {
+        if !local.contains_key(&entry.uuid) {
+            if let Some(version) = local.remove(from) {
+                db.rekey_sticky(from, &entry.uuid)?;
+                local.insert(entry.uuid.clone(), version);
+            }
+        }
+    }
+
+    let mut version = PublishedVersion {
+        content_hash: entry.content_hash.clone(),
+        metadata_hash: entry.plist.clone(),
+        tags_hash: tags_hash(&entry.manual_tags),
+    };
+    let mut version_vector = entry.version_vector.clone();
+    match local.get_mut(&entry.uuid) {
+        // Same note; only the window may have moved
+        Some(current) if current.content_hash == entry.content_hash => {
+            let current_plist = blob_id(&current.plist_metadata);
+            let last_published = published.get(&entry.uuid).map(|p| p.metadata_hash.as_str());
+            let moved = current_plist != entry.plist && !plist_metadata.is_empty();
+            if moved && takes_layout(&current_plist, last_published, entry) {
+                let metadata = StickyMetadata::from_bytes(&plist_metadata)?;
+                plist_metadata = metadata.to_bytes()?;
+                version.metadata_hash = blob_id(&plist_metadata);
+                db.update_metadata(&entry.uuid, &metadata)?;
+            } else if moved {
+                // Ours is newer; publishing goes on as if the entry weren't there
+                version.metadata_hash = last_published.unwrap_or_default().to_string();
+                plist_metadata = current.plist_metadata.clone();
+            }
+            version_vector = current.version_vector.merged(&entry.version_vector);
+            if version_vector != current.version_vector {
+                db.set_version_vector(&entry.uuid, &version_vector)?;
+            }
+        }
+        Some(current) if !takes_entry(current, entry, conflicts) => {
+            // Our copy wins a concurrent edit; it now includes both histories,
+            // so the other machine takes it when it merges our log
+            let merged = current.version_vector.merged(&entry.version_vector);
+            if merged != current.version_vector {
+                db.set_version_vector(&entry.uuid, &merged)?;
+                current.version_vector = merged;
+            }
+            add_manual_tags(db, entry)?;
+            return Ok(true);
+        }
+        current => {
+            if let Some(current) = current {
+                version_vector = version_vector.merged(&current.version_vector);
+            }
+            // Logs never carry attachments over the size limit; the records
+            // of this machine's stay so its copies are kept
+            let skipped = db
+                .get_attachment_info(&entry.uuid)?
+                .into_iter()
+                .filter(|info| {
+                    !info.stored && !attachments.iter().any(|a| a.filename == info.filename)
+                })
+                .collect();
+            let record = StickyRecord {
+                sticky: crate::database::Sticky {
+                    uuid: entry.uuid.clone(),
+                    content_text: rtf::extract_text_from_bytes(&rtf_data),
+                    rtf_data,
+                    plist_metadata: plist_metadata.clone(),
+                    is_floating: entry.is_floating,
+                    color: entry.color.clone(),
+                    modified_at: entry.modified_at,
+                    created_at: entry.created_at,
+                    source_machine: entry.source_machine.clone(),
+                    modified_ns: entry.modified_ns,
+                    content_hash: entry.content_hash.clone(),
+                    version_vector: version_vector.clone(),
+                },
+                attachments,
+                skipped,
+            };
+            db.insert_batch(&[record])?;
+        }
+    }
+
+    // Tags are merged rather than replaced. If this machine has some the
+    // entry lacks, its tags no longer match the hash recorded below, so
+    // they are published back.
+    add_manual_tags(db, entry)?;
+
+    // Already in the logs, so this machine doesn't publish it again
+    db.set_published(&entry.uuid, &version)?;
+    published.insert(entry.uuid.clone(), version);
+    local.insert(
+        entry.uuid.clone(),
+        StoredVersion {
+            modified_at: entry.modified_at,
+            modified_ns: entry.modified_ns,
+            content_hash: entry.content_hash.clone(),
+            source_machine: entry.source_machine.clone(),
+            plist_metadata,
+            version_vector,
+        },
+    );
+    Ok(true)
+}
+
+/// Whether an entry with different content should replace the local copy.
+/// Version vectors decide when both copies have one. Copies from older
+/// versions fall back to the newer timestamp, as do concurrent edits, which
+/// break ties by hash so every machine keeps the same copy.
+fn takes_entry(
+    current: &StoredVersion,
+    entry: &ChangeEntry,
+    conflicts: &mut Vec<Conflict>,
+) -> bool {
+    let local_ns = runner::modified_ns(current.modified_ns, current.modified_at);
+    if current.version_vector.is_empty() || entry.version_vector.is_empty() {
+        return entry.modified_ns > local_ns;
+    }
+
+    match entry.version_vector.compare(&current.version_vector) {
+        Causality::After => true,
+        Causality::Before | Causality::Equal => false,
+        Causality::Concurrent => {
+            let theirs_newer =
+                (entry.modified_ns, &entry.content_hash) > (local_ns, &current.content_hash);
+            let detail = if theirs_newer {
+                format!(
+                    "edited here and on {}; kept the newer copy from {}",
+                    entry.source_machine, entry.source_machine
+                )
+            } else {
+                format!(
+                    "edited here and on {}; kept this machine's newer copy",
+                    entry.source_machine
+                )
+            };
+            conflicts.push(Conflict {
+                uuid: entry.uuid.clone(),
+                detail,
+            });
+            theirs_newer
+        }
+    }
+}
+
+/// Whether an entry's window state should replace the local one for the same
+/// content. An entry made from the state this machine has is newer than it;
+/// otherwise both moved the window and the higher blob id wins, so every
+/// machine keeps the same one. Entries from older versions don't say what
+/// they were made from, so they lose only to a local move not yet published.
+fn takes_layout(current_plist: &str, last_published: Option<&str>, entry: &ChangeEntry) -> bool {
+    match &entry.previous_plist {
+        Some(previous) if previous == current_plist => true,
+        Some(_) => entry.plist.as_str() > current_plist,
+        None => last_published.is_none_or(|published| published == current_plist),
+    }
+}
+
+/// Add an entry's manual tags to the local copy, keeping any it already has
+fn add_manual_tags(db: &Database, entry: &ChangeEntry) -> Result<()> {
+    for tag in &entry.manual_tags {
+        db.add_tag(&entry.uuid, tag)?;
+    }
+    Ok(())
+}
+
+/// Identifies a sorted set of manual tags; empty for none, which matches
+/// versions published before tags were
+fn tags_hash(tags: &[String]) -> String {
+    if tags.is_empty() {
+        String::new()
+    } else {
+        blob_id(tags.join("\n").as_bytes())
+    }
+}
+
+/// Blobs are named by the SHA-256 of their content
+fn blob_id(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Store content as a blob unless it already exists, returning its id. The
+/// file appears under its final name only once fully written.
+fn write_blob(shared_dir: &Path, content: &[u8]) -> Result<String> {
+    let id = blob_id(content);
+    let dir = shared_dir.join(BLOBS_DIR);
+    let path = dir.join(&id);
+    if !path.exists() {
+        fs::create_dir_all(&dir)?;
+        let tmp_path = dir.join(format!(".{}.tmp-{}", id, std::process::id()));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+    }
+    Ok(id)
+}
+
+/// A blob's content, or None when it hasn't synced here yet (or arrived
+/// damaged, in which case a later sync may bring the full file)
+fn read_blob(shared_dir: &Path, id: &str) -> Result<Option<Vec<u8>>> {
+    let path = shared_dir.join(BLOBS_DIR).join(id);
+    match fs::read(&path) {
+        Ok(content) if blob_id(&content) == id => Ok(Some(content)),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,120 @@
+// ABOUTME: Watch mode that syncs whenever Stickies.app or another machine changes something
+// ABOUTME: Debounces filesystem events from the Stickies directory and polls the database or change logs
+
+use super::runner::{self, SyncOptions, SyncReport};
+use super::shared;
+use crate::database::Database;
+use crate::{Result, StickyError};
+use notify::RecursiveMode;
+use notify_debouncer_mini::new_debouncer;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, SystemTime};
+
+pub struct WatchOptions {
+    /// Quiet period after the last filesystem event before syncing, so a
+    /// burst of keystrokes in Stickies.app becomes one sync
+    pub debounce: Duration,
+    /// How often to check the database file for changes from other machines
+    pub poll_interval: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(500),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Size and modification time of the database file and its WAL, used to
+/// notice writes made by other machines (e.g. through iCloud Drive). In
+/// shared mode the change logs are included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseStamp(Vec<Option<(u64, SystemTime)>>);
+
+impl DatabaseStamp {
+    pub fn read(database_path: &Path, shared_dir: Option<&Path>) -> Self {
+        let mut paths = vec![
+            database_path.to_path_buf(),
+            PathBuf::from(format!("{}-wal", database_path.display())),
+        ];
+        if let Some(shared_dir) = shared_dir {
+            let changes_dir = shared_dir.join(shared::CHANGES_DIR);
+            if let Ok(entries) = fs::read_dir(changes_dir) {
+                let mut logs: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+                logs.sort();
+                paths.extend(logs);
+            }
+        }
+
+        let stamp = paths
+            .iter()
+            .map(|path| {
+                let metadata = fs::metadata(path).ok()?;
+                Some((metadata.len(), metadata.modified().ok()?))
+            })
+            .collect();
+        Self(stamp)
+    }
+}
+
+/// Whether a change at `path` can affect a sync. Our own temporary files
+/// (bundle staging directories, state file replacements) are ignored.
+pub fn is_relevant(path: &Path) -> bool {
+    path.components().all(|component| {
+        let name = component.as_os_str().to_string_lossy();
+        !(name.contains(".tmp-") || name.contains(".old-") || name.ends_with(".tmp"))
+    })
+}
+
+/// Sync once, then again after every relevant change, until the watcher
+/// stops. `on_sync` is called with the outcome of every sync; a failed sync
+/// doesn't end the watch.
+pub fn watch(
+    database_path: &Path,
+    stickies_path: &Path,
+    sync_options: &SyncOptions,
+    watch_options: &WatchOptions,
+    mut on_sync: impl FnMut(Result<SyncReport>),
+) -> Result<()> {
+    let db = Database::create(database_path)?;
+    let shared_dir = sync_options.shared_dir.as_deref();
+
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(watch_options.debounce, tx).map_err(notify_error)?;
+    debouncer
+        .watcher()
+        .watch(stickies_path, RecursiveMode::Recursive)
+        .map_err(notify_error)?;
+
+    on_sync(runner::run(&db, stickies_path, sync_options));
+    let mut db_stamp = DatabaseStamp::read(database_path, shared_dir);
+
+    loop {
+        let should_sync = match rx.recv_timeout(watch_options.poll_interval) {
+            Ok(Ok(events)) => events.iter().any(|event| is_relevant(&event.path)),
+            Ok(Err(e)) => {
+                on_sync(Err(notify_error(e)));
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                DatabaseStamp::read(database_path, shared_dir) != db_stamp
+            }
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+
+        if should_sync {
+            // Our own writes echo back as events; that follow-up sync finds
+            // nothing to do, so it can't loop
+            on_sync(runner::run(&db, stickies_path, sync_options));
+            db_stamp = DatabaseStamp::read(database_path, shared_dir);
+        }
+    }
+}
+
+fn notify_error(err: notify::Error) -> StickyError {
+    StickyError::Io(std::io::Error::other(err))
+}
@@ -0,0 +1,38 @@
+// ABOUTME: Tag name handling for stickies
+// ABOUTME: Normalizes tag names and extracts #hashtags from sticky text
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+static HASHTAG_RE: OnceLock<Regex> = OnceLock::new();
+
+/// Normalize a user-supplied tag name: strips a leading `#` and lowercases.
+/// Returns None for names that are empty or contain characters other than
+/// letters, digits, `_` and `-`.
+pub fn normalize_tag(name: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches('#').to_lowercase();
+
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+
+    valid.then_some(name)
+}
+
+/// Extract `#hashtags` from plain text, normalized, sorted and deduplicated.
+/// Tags must contain at least one letter, so `#1` or `#2026` are ignored.
+pub fn extract_hashtags(text: &str) -> Vec<String> {
+    // The hashtag must start a word, so "C#" or "&#123;" are not tags
+    let hashtag_re = HASHTAG_RE.get_or_init(|| Regex::new(r"(?:^|[^\w&#])#([\w][\w-]*)").unwrap());
+
+    let mut tags: Vec<String> = hashtag_re
+        .captures_iter(text)
+        .filter_map(|caps| normalize_tag(&caps[1]))
+        .filter(|tag| tag.chars().any(char::is_alphabetic))
+        .collect();
+
+    tags.sort();
+    tags.dedup();
+    tags
+}
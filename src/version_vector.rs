@@ -0,0 +1,92 @@
+// ABOUTME: Version vectors recording which machines' edits a sticky's content includes
+// ABOUTME: Lets sync tell a concurrent edit from a stale copy without trusting clocks
+
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How two versions of a sticky relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Causality {
+    Equal,
+    /// The first version is an ancestor of the second
+    Before,
+    /// The first version includes every edit of the second
+    After,
+    /// Each has edits the other lacks
+    Concurrent,
+}
+
+/// Number of content edits made on each machine, keyed by machine ID. An
+/// empty vector means the history is unknown (rows from older versions).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VersionVector(BTreeMap<String, u64>);
+
+impl VersionVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, machine: &str) -> u64 {
+        self.0.get(machine).copied().unwrap_or(0)
+    }
+
+    /// Record an edit made on `machine`
+    pub fn increment(&mut self, machine: &str) {
+        *self.0.entry(machine.to_string()).or_insert(0) += 1;
+    }
+
+    /// The vector of a version that includes both histories
+    pub fn merged(&self, other: &Self) -> Self {
+        let mut merged = self.clone();
+        for (machine, &count) in &other.0 {
+            let entry = merged.0.entry(machine.clone()).or_insert(0);
+            *entry = (*entry).max(count);
+        }
+        merged
+    }
+
+    pub fn compare(&self, other: &Self) -> Causality {
+        let machines = self.0.keys().chain(other.0.keys());
+        let (mut ahead, mut behind) = (false, false);
+        for machine in machines {
+            let (ours, theirs) = (self.get(machine), other.get(machine));
+            ahead |= ours > theirs;
+            behind |= ours < theirs;
+        }
+        match (ahead, behind) {
+            (false, false) => Causality::Equal,
+            (false, true) => Causality::Before,
+            (true, false) => Causality::After,
+            (true, true) => Causality::Concurrent,
+        }
+    }
+
+    /// Stored as a JSON object, e.g. `{"3f2a...":2,"9b1c...":1}`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap_or_default()
+    }
+
+    /// Anything that isn't a valid vector (including the empty string older
+    /// rows have) reads as an unknown history
+    pub fn from_json(text: &str) -> Self {
+        serde_json::from_str(text).unwrap_or_default()
+    }
+}
+
+impl ToSql for VersionVector {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_json()))
+    }
+}
+
+impl FromSql for VersionVector {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Ok(Self::from_json(value.as_str()?))
+    }
+}
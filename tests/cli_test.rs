@@ -0,0 +1,37 @@
+// ABOUTME: Tests for the sticky-sync command line
+// ABOUTME: Runs the built binary to check argument parsing and error output
+
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Run the binary with an empty home so no real config is read
+fn sticky(args: &[&str]) -> Output {
+    let home = TempDir::new().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_sticky-sync"))
+        .args(args)
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join("config"))
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_search_accepts_negated_first_term() {
+    // The bad color fails after parsing, before any database is opened
+    let output = sticky(&["search", "-draft", "--color", "orange"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!stderr.contains("unexpected argument"), "{}", stderr);
+    assert!(stderr.contains("Invalid input"), "{}", stderr);
+}
+
+#[test]
+fn test_errors_are_printed_readably_with_exit_code_1() {
+    let output = sticky(&["search", "budget", "--color", "orange"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("Error: Invalid input: "), "{}", stderr);
+    assert!(!stderr.contains("InvalidInput("), "{}", stderr);
+}
@@ -0,0 +1,44 @@
+use sticky_situation::filesystem::StickyColor;
+use sticky_situation::query::SearchQuery;
+use sticky_situation::StickyError;
+
+#[test]
+fn test_color_index_roundtrip() {
+    for index in -1..10 {
+        assert_eq!(StickyColor::from_index(index).index(), index);
+    }
+    assert_eq!(StickyColor::from_index(3), StickyColor::Pink);
+    assert_eq!(StickyColor::from_index(42), StickyColor::Unknown(42));
+}
+
+#[test]
+fn test_color_name_roundtrip() {
+    for color in StickyColor::ALL {
+        assert_eq!(color.to_string().parse::<StickyColor>(), Ok(color));
+        assert!(color.hex().is_some());
+    }
+
+    let unknown = StickyColor::Unknown(9);
+    assert_eq!(unknown.to_string(), "color-9");
+    assert_eq!("color-9".parse::<StickyColor>(), Ok(unknown));
+    assert_eq!(unknown.hex(), None);
+}
+
+#[test]
+fn test_parse_color_names() {
+    assert_eq!("Pink".parse::<StickyColor>(), Ok(StickyColor::Pink));
+    assert_eq!("grey".parse::<StickyColor>(), Ok(StickyColor::Gray));
+    assert_eq!(StickyColor::Blue.hex(), Some("#ADF4FF"));
+
+    let err = "orange".parse::<StickyColor>().unwrap_err();
+    assert!(err.contains("orange"));
+    assert!(err.contains("purple"));
+}
+
+#[test]
+fn test_query_rejects_unknown_color() {
+    match SearchQuery::parse("notes color:orange") {
+        Err(StickyError::InvalidQuery { message, .. }) => assert!(message.contains("orange")),
+        other => panic!("expected InvalidQuery, got {:?}", other),
+    }
+}
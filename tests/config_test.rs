@@ -1,5 +1,6 @@
 use std::fs;
 use sticky_situation::config::Config;
+use sticky_situation::StickyError;
 use tempfile::tempdir;
 
 #[test]
@@ -75,3 +76,137 @@ fn test_config_default_values() {
         .unwrap()
         .ends_with("conflicts.log"));
 }
+
+#[test]
+fn test_max_attachment_bytes_defaults_when_missing() {
+    let config: Config = toml::from_str(
+        r#"
+        database_path = "/tmp/test.db"
+        log_conflicts = false
+        conflict_log_path = "/tmp/conflicts.log"
+    "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.max_attachment_bytes,
+        sticky_situation::config::DEFAULT_MAX_ATTACHMENT_BYTES
+    );
+}
+
+#[test]
+fn test_shared_dir_is_optional() {
+    let config: Config = toml::from_str(
+        r#"
+        database_path = "/tmp/test.db"
+        log_conflicts = true
+        conflict_log_path = "/tmp/conflicts.log"
+    "#,
+    )
+    .unwrap();
+    assert_eq!(config.shared_dir, None);
+    assert!(!toml::to_string(&config).unwrap().contains("shared_dir"));
+
+    let config: Config = toml::from_str(
+        r#"
+        database_path = "/tmp/test.db"
+        log_conflicts = true
+        conflict_log_path = "/tmp/conflicts.log"
+        shared_dir = "/tmp/iCloud/sticky-situation"
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.shared_dir,
+        Some(std::path::PathBuf::from("/tmp/iCloud/sticky-situation"))
+    );
+}
+
+#[test]
+fn test_machine_id_is_generated_once_and_saved() {
+    let dir = tempdir().unwrap();
+    let config_file = dir.path().join("config.toml");
+    fs::write(
+        &config_file,
+        r#"# My settings
+database_path = "/tmp/test.db"
+log_conflicts = true
+conflict_log_path = "/tmp/conflicts.log"
+"#,
+    )
+    .unwrap();
+
+    let mut config: Config = toml::from_str(&fs::read_to_string(&config_file).unwrap()).unwrap();
+    assert_eq!(config.machine_id, None);
+    let id = config.ensure_machine_id(&config_file).unwrap();
+    assert_eq!(config.ensure_machine_id(&config_file).unwrap(), id);
+
+    // Added to the file without disturbing what was there
+    let contents = fs::read_to_string(&config_file).unwrap();
+    assert!(contents.starts_with("# My settings"));
+    let mut reloaded: Config = toml::from_str(&contents).unwrap();
+    assert_eq!(reloaded.machine_id.as_deref(), Some(id.as_str()));
+    assert_eq!(reloaded.ensure_machine_id(&config_file).unwrap(), id);
+
+    // A missing config file is created
+    let new_file = dir.path().join("new").join("config.toml");
+    let id = Config::default().ensure_machine_id(&new_file).unwrap();
+    let created: Config = toml::from_str(&fs::read_to_string(&new_file).unwrap()).unwrap();
+    assert_eq!(created.machine_id, Some(id));
+}
+
+#[test]
+fn test_machine_id_is_shared_by_commands_starting_together() {
+    let dir = tempdir().unwrap();
+    let config_file = dir.path().join("config.toml");
+    fs::write(&config_file, "database_path = \"/tmp/test.db\"\nlog_conflicts = true\nconflict_log_path = \"/tmp/conflicts.log\"\n").unwrap();
+    let config: Config = toml::from_str(&fs::read_to_string(&config_file).unwrap()).unwrap();
+
+    let ids: Vec<String> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let mut config = config.clone();
+                let config_file = &config_file;
+                scope.spawn(move || config.ensure_machine_id(config_file).unwrap())
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    assert!(ids.iter().all(|id| *id == ids[0]));
+    let contents = fs::read_to_string(&config_file).unwrap();
+    assert_eq!(contents.matches("machine_id").count(), 1);
+}
+
+#[test]
+fn test_machine_id_is_added_at_top_level_after_a_table() {
+    let dir = tempdir().unwrap();
+    let config_file = dir.path().join("config.toml");
+    fs::write(
+        &config_file,
+        "database_path = \"/tmp/test.db\"\nlog_conflicts = true\nconflict_log_path = \"/tmp/conflicts.log\"\n\n# Notes for later\n[extra]\nkey = \"value\"\n",
+    )
+    .unwrap();
+
+    let id = Config::default().ensure_machine_id(&config_file).unwrap();
+
+    let contents = fs::read_to_string(&config_file).unwrap();
+    assert!(contents.contains("# Notes for later"));
+    let reloaded: Config = toml::from_str(&contents).unwrap();
+    assert_eq!(reloaded.machine_id, Some(id));
+}
+
+#[test]
+fn test_machine_id_is_not_written_to_an_invalid_config() {
+    let dir = tempdir().unwrap();
+    let config_file = dir.path().join("config.toml");
+    fs::write(&config_file, "database_path = \n").unwrap();
+
+    let result = Config::default().ensure_machine_id(&config_file);
+
+    assert!(matches!(result, Err(StickyError::Config(_))));
+    assert_eq!(
+        fs::read_to_string(&config_file).unwrap(),
+        "database_path = \n"
+    );
+}
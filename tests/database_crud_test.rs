@@ -1,4 +1,7 @@
-use sticky_situation::database::{Database, Sticky};
+use std::collections::HashMap;
+use sticky_situation::database::{Database, ScanEntry, Sticky, StickyRecord};
+use sticky_situation::filesystem::rtfd::Attachment;
+use sticky_situation::version_vector::VersionVector;
 use tempfile::tempdir;
 
 #[test]
@@ -11,10 +14,14 @@ fn test_insert_sticky() {
         content_text: "Hello world".to_string(),
         rtf_data: b"rtf data".to_vec(),
         plist_metadata: b"plist data".to_vec(),
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1234567890,
         created_at: 1234567890,
         source_machine: "test-machine".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -33,10 +40,14 @@ fn test_search_stickies() {
         content_text: "Meeting notes for project".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 1234567890,
         created_at: 1234567890,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -65,10 +76,14 @@ fn test_get_all_uuids() {
         content_text: "First sticky".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     let sticky2 = Sticky {
@@ -76,10 +91,14 @@ fn test_get_all_uuids() {
         content_text: "Second sticky".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 2000,
         created_at: 2000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky1).unwrap();
@@ -102,10 +121,14 @@ fn test_insert_or_replace() {
         content_text: "Original content".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky1).unwrap();
@@ -116,10 +139,14 @@ fn test_insert_or_replace() {
         content_text: "Updated content".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 2000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky2).unwrap();
@@ -145,10 +172,14 @@ fn test_fts5_index_sync() {
         content_text: "This has searchable keywords".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -164,10 +195,14 @@ fn test_fts5_index_sync() {
         content_text: "This has different words now".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 2000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&updated).unwrap();
@@ -192,10 +227,14 @@ fn test_search_multiple_results() {
         content_text: "Important meeting tomorrow".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     let sticky2 = Sticky {
@@ -203,10 +242,14 @@ fn test_search_multiple_results() {
         content_text: "Meeting notes from last week".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 2000,
         created_at: 2000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     let sticky3 = Sticky {
@@ -214,10 +257,14 @@ fn test_search_multiple_results() {
         content_text: "Grocery list: milk, eggs, bread".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "green".to_string(),
         modified_at: 3000,
         created_at: 3000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky1).unwrap();
@@ -242,10 +289,14 @@ fn test_search_no_results() {
         content_text: "Nothing to see here".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -253,3 +304,205 @@ fn test_search_no_results() {
     let results = db.search("nonexistent").unwrap();
     assert_eq!(results.len(), 0);
 }
+
+#[test]
+fn test_search_ranked_orders_by_relevance() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let entries = [
+        (
+            "weak",
+            "A long note that mentions budget once among many other words here",
+        ),
+        ("strong", "budget budget budget"),
+    ];
+
+    for (uuid, content) in entries {
+        db.insert_sticky(&Sticky {
+            uuid: uuid.to_string(),
+            content_text: content.to_string(),
+            rtf_data: vec![],
+            plist_metadata: vec![],
+            is_floating: false,
+            color: "yellow".to_string(),
+            modified_at: 1000,
+            created_at: 1000,
+            source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
+        })
+        .unwrap();
+    }
+
+    let hits = db.search_ranked("budget", None, None, 0).unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].sticky.uuid, "strong");
+    assert!(hits[0].rank <= hits[1].rank);
+}
+
+#[test]
+fn test_search_ranked_snippet_highlights_match() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    db.insert_sticky(&Sticky {
+        uuid: "snippet".to_string(),
+        content_text: "Remember to water the plants on Friday".to_string(),
+        rtf_data: vec![],
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1000,
+        created_at: 1000,
+        source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+
+    let hits = db.search_ranked("plants", None, None, 0).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].snippet.contains("[plants]"));
+    assert_eq!(
+        hits[0].highlighted,
+        "Remember to water the [plants] on Friday"
+    );
+}
+
+#[test]
+fn test_search_ranked_limit_offset_and_color() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    for i in 0..5 {
+        db.insert_sticky(&Sticky {
+            uuid: format!("page-{}", i),
+            content_text: format!("todo item number {}", i),
+            rtf_data: vec![],
+            plist_metadata: vec![],
+            is_floating: false,
+            color: if i % 2 == 0 { "yellow" } else { "blue" }.to_string(),
+            modified_at: 1000,
+            created_at: 1000,
+            source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
+        })
+        .unwrap();
+    }
+
+    let first_page = db.search_ranked("todo", None, Some(2), 0).unwrap();
+    let second_page = db.search_ranked("todo", None, Some(2), 2).unwrap();
+    let last_page = db.search_ranked("todo", None, Some(2), 4).unwrap();
+    assert_eq!(first_page.len(), 2);
+    assert_eq!(second_page.len(), 2);
+    assert_eq!(last_page.len(), 1);
+    assert!(first_page
+        .iter()
+        .all(|a| second_page.iter().all(|b| a.sticky.uuid != b.sticky.uuid)));
+
+    let blue = db.search_ranked("todo", Some("blue"), None, 0).unwrap();
+    assert_eq!(blue.len(), 2);
+    assert!(blue.iter().all(|hit| hit.sticky.color == "blue"));
+}
+
+#[test]
+fn test_get_modified_times() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    db.insert_sticky(&Sticky {
+        uuid: "timed".to_string(),
+        content_text: "Timed".to_string(),
+        rtf_data: b"large rtf".to_vec(),
+        plist_metadata: b"plist".to_vec(),
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1700000000,
+        created_at: 1700000000,
+        source_machine: "laptop".to_string(),
+        modified_ns: 1_700_000_000_000_000_123,
+        content_hash: "abc".to_string(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+
+    let times = db.get_modified_times().unwrap();
+    assert_eq!(times.len(), 1);
+    let stored = &times["timed"];
+    assert_eq!(stored.modified_at, 1700000000);
+    assert_eq!(stored.modified_ns, 1_700_000_000_000_000_123);
+    assert_eq!(stored.content_hash, "abc");
+    assert_eq!(stored.source_machine, "laptop");
+    assert_eq!(stored.plist_metadata, b"plist");
+}
+
+#[test]
+fn test_scan_cache_is_per_machine() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let entry = ScanEntry {
+        modified_ns: 42,
+        content_hash: "abc".to_string(),
+    };
+    db.save_scan_cache(
+        "laptop",
+        &HashMap::from([("one".to_string(), entry.clone())]),
+    )
+    .unwrap();
+    db.save_scan_cache("desktop", &HashMap::new()).unwrap();
+
+    assert_eq!(db.get_scan_cache("laptop").unwrap()["one"], entry);
+    assert!(db.get_scan_cache("desktop").unwrap().is_empty());
+
+    // Saving replaces the machine's previous entries
+    db.save_scan_cache("laptop", &HashMap::new()).unwrap();
+    assert!(db.get_scan_cache("laptop").unwrap().is_empty());
+}
+
+#[test]
+fn test_insert_batch() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let records: Vec<StickyRecord> = (0..3)
+        .map(|i| StickyRecord {
+            sticky: Sticky {
+                uuid: format!("batch-{}", i),
+                content_text: format!("Batch note {}", i),
+                rtf_data: vec![],
+                plist_metadata: vec![],
+                is_floating: false,
+                color: "yellow".to_string(),
+                modified_at: 1700000000,
+                created_at: 1700000000,
+                source_machine: "test-machine".to_string(),
+                modified_ns: 0,
+                content_hash: String::new(),
+                version_vector: VersionVector::new(),
+            },
+            attachments: vec![Attachment {
+                filename: "image.png".to_string(),
+                content: b"png".to_vec(),
+            }],
+            skipped: vec![],
+        })
+        .collect();
+
+    db.insert_batch(&records).unwrap();
+
+    assert_eq!(db.get_all_uuids().unwrap().len(), 3);
+    assert_eq!(db.get_attachments("batch-2").unwrap().len(), 1);
+    assert_eq!(db.search("Batch").unwrap().len(), 3);
+}
+
+#[test]
+fn test_database_can_be_shared_between_threads() {
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<Database>();
+}
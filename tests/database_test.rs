@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::Path;
 use sticky_situation::database::{Database, Sticky};
+use sticky_situation::version_vector::VersionVector;
 use tempfile::tempdir;
 
 #[test]
@@ -20,7 +21,7 @@ fn test_schema_created() {
     let db = Database::create(&db_path).unwrap();
 
     // Verify tables exist
-    let conn = db.connection().borrow();
+    let conn = db.connection().lock().unwrap();
     let mut stmt = conn
         .prepare("SELECT name FROM sqlite_master WHERE type='table'")
         .unwrap();
@@ -53,10 +54,14 @@ fn test_search_with_special_characters() {
         content_text: "Test with special chars: @#$% & * () []".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
     db.insert_sticky(&sticky).unwrap();
 
@@ -77,10 +82,14 @@ fn test_search_with_quotes() {
         content_text: r#"This has "quoted text" in it"#.to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
     db.insert_sticky(&sticky).unwrap();
 
@@ -100,10 +109,14 @@ fn test_database_insert_and_retrieve() {
         content_text: "Test content".to_string(),
         rtf_data: vec![1, 2, 3],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 5000,
         created_at: 1000,
         source_machine: "machine1".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -128,10 +141,14 @@ fn test_database_update_sticky() {
         content_text: "Original content".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
     db.insert_sticky(&sticky).unwrap();
 
@@ -141,10 +158,14 @@ fn test_database_update_sticky() {
         content_text: "Updated content".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 2000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
     db.insert_sticky(&updated).unwrap();
 
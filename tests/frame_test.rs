@@ -0,0 +1,58 @@
+use sticky_situation::filesystem::frame::{parse_position, parse_screen, parse_size, Frame};
+
+#[test]
+fn test_parse_frame() {
+    let frame: Frame = "{{100, 200}, {300, 400}}".parse().unwrap();
+    assert_eq!(frame, Frame::new(100.0, 200.0, 300.0, 400.0));
+}
+
+#[test]
+fn test_parse_frame_fractional_and_compact() {
+    let frame: Frame = "{{12.5,-40},{250.25,99}}".parse().unwrap();
+    assert_eq!(frame, Frame::new(12.5, -40.0, 250.25, 99.0));
+}
+
+#[test]
+fn test_parse_invalid_frame() {
+    assert!("".parse::<Frame>().is_err());
+    assert!("{{1, 2}, {3}}".parse::<Frame>().is_err());
+    assert!("{{a, b}, {c, d}}".parse::<Frame>().is_err());
+    assert!("1, 2, 3, 4".parse::<Frame>().is_err());
+}
+
+#[test]
+fn test_frame_display_roundtrip() {
+    for s in ["{{100, 100}, {250, 250}}", "{{-12.5, 0}, {300.75, 200}}"] {
+        let frame: Frame = s.parse().unwrap();
+        assert_eq!(frame.to_string(), s);
+    }
+}
+
+#[test]
+fn test_frame_with_position_and_size() {
+    let frame = Frame::default()
+        .with_size(300.0, 200.0)
+        .with_position(40.0, 40.0);
+    assert_eq!(frame, Frame::new(40.0, 40.0, 300.0, 200.0));
+}
+
+#[test]
+fn test_parse_size_and_position() {
+    assert_eq!(parse_size("300x200"), Some((300.0, 200.0)));
+    assert_eq!(parse_size("300X200"), Some((300.0, 200.0)));
+    assert_eq!(parse_size("0x200"), None);
+    assert_eq!(parse_size("300"), None);
+    assert_eq!(parse_position("40,40"), Some((40.0, 40.0)));
+    assert_eq!(parse_position("-10, 25.5"), Some((-10.0, 25.5)));
+    assert_eq!(parse_position("40"), None);
+}
+
+#[test]
+fn test_parse_rejects_non_finite_values() {
+    assert_eq!(parse_size("infx200"), None);
+    assert_eq!(parse_size("300xNaN"), None);
+    assert_eq!(parse_position("NaN,0"), None);
+    assert_eq!(parse_position("0,-inf"), None);
+    assert_eq!(parse_screen("NaN,0,1920x1080"), None);
+    assert!("{{NaN, 0}, {250, 250}}".parse::<Frame>().is_err());
+}
@@ -2,6 +2,7 @@ use sticky_situation::{
     database::{Database, Sticky},
     filesystem::rtfd::RtfdBundle,
     rtf,
+    version_vector::VersionVector,
 };
 use tempfile::tempdir;
 
@@ -34,10 +35,14 @@ fn test_search_integration() {
         content_text: "Find this unique phrase".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -75,10 +80,14 @@ fn test_rtfd_to_database_to_search() {
         content_text: extracted_text.clone(),
         rtf_data: read_bundle.rtf_data.clone(),
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 1234567890,
         created_at: 1234567890,
         source_machine: "integration-test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -107,30 +116,42 @@ fn test_multiple_stickies_search() {
             content_text: "Buy groceries: milk, eggs, bread".to_string(),
             rtf_data: vec![],
             plist_metadata: vec![],
+            is_floating: false,
             color: "yellow".to_string(),
             modified_at: 1000,
             created_at: 1000,
             source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
         },
         Sticky {
             uuid: "sticky-2".to_string(),
             content_text: "Meeting at 3pm tomorrow".to_string(),
             rtf_data: vec![],
             plist_metadata: vec![],
+            is_floating: false,
             color: "blue".to_string(),
             modified_at: 2000,
             created_at: 2000,
             source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
         },
         Sticky {
             uuid: "sticky-3".to_string(),
             content_text: "Call mom about her birthday".to_string(),
             rtf_data: vec![],
             plist_metadata: vec![],
+            is_floating: false,
             color: "pink".to_string(),
             modified_at: 3000,
             created_at: 3000,
             source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
         },
     ];
 
@@ -181,10 +202,14 @@ fn test_rtfd_with_attachments_workflow() {
         content_text: extracted_text,
         rtf_data: read_bundle.rtf_data,
         plist_metadata: vec![],
+        is_floating: false,
         color: "green".to_string(),
         modified_at: 5000,
         created_at: 5000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -206,10 +231,14 @@ fn test_update_sticky_preserves_search() {
         content_text: "Original content".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky_v1).unwrap();
@@ -224,10 +253,14 @@ fn test_update_sticky_preserves_search() {
         content_text: "Updated content with new information".to_string(),
         rtf_data: vec![],
         plist_metadata: vec![],
+        is_floating: false,
         color: "blue".to_string(),
         modified_at: 2000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky_v2).unwrap();
@@ -274,10 +307,14 @@ fn test_rtfd_roundtrip_preserves_content() {
         content_text: extracted,
         rtf_data: read_bundle.rtf_data.clone(),
         plist_metadata: vec![],
+        is_floating: false,
         color: "yellow".to_string(),
         modified_at: 1000,
         created_at: 1000,
         source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
     };
 
     db.insert_sticky(&sticky).unwrap();
@@ -312,10 +349,14 @@ fn test_database_persistence() {
             content_text: "This should persist".to_string(),
             rtf_data: vec![],
             plist_metadata: vec![],
+            is_floating: false,
             color: "yellow".to_string(),
             modified_at: 1000,
             created_at: 1000,
             source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
         };
         db.insert_sticky(&sticky).unwrap();
     }
@@ -0,0 +1,244 @@
+use plist::{Dictionary, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use sticky_situation::database::{Database, LayoutEntry};
+use sticky_situation::filesystem::{
+    self, frame::parse_screen, plist::read_stickies_state, Frame, StickyMetadata,
+};
+use sticky_situation::layout::{self, Arrangement};
+use tempfile::tempdir;
+
+fn write_state(stickies_path: &Path, frames: &[(&str, &str)]) {
+    let entries = frames
+        .iter()
+        .map(|(uuid, frame)| {
+            let mut entry = Dictionary::new();
+            entry.insert("UUID".to_string(), Value::String(uuid.to_string()));
+            entry.insert("Frame".to_string(), Value::String(frame.to_string()));
+            entry.insert("Floating".to_string(), Value::Boolean(false));
+            Value::Dictionary(entry)
+        })
+        .collect();
+    plist::to_file_binary(
+        stickies_path.join(filesystem::SAVED_STATE_FILE),
+        &Value::Array(entries),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_frame_clamped_to_screen() {
+    let screen = Frame::new(0.0, 0.0, 1440.0, 900.0);
+
+    let offscreen = Frame::new(2500.0, -300.0, 250.0, 250.0);
+    assert_eq!(
+        offscreen.clamped_to(&screen),
+        Frame::new(1190.0, 0.0, 250.0, 250.0)
+    );
+
+    let too_big = Frame::new(10.0, 10.0, 2000.0, 250.0);
+    assert_eq!(
+        too_big.clamped_to(&screen),
+        Frame::new(0.0, 10.0, 1440.0, 250.0)
+    );
+
+    let inside = Frame::new(100.0, 100.0, 250.0, 250.0);
+    assert_eq!(inside.clamped_to(&screen), inside);
+}
+
+#[test]
+fn test_parse_screen() {
+    assert_eq!(
+        parse_screen("1920x1080"),
+        Some(Frame::new(0.0, 0.0, 1920.0, 1080.0))
+    );
+    assert_eq!(
+        parse_screen("-1920,0,1920x1080"),
+        Some(Frame::new(-1920.0, 0.0, 1920.0, 1080.0))
+    );
+    assert_eq!(parse_screen("wide"), None);
+}
+
+#[test]
+fn test_layout_save_and_load() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let entries = vec![LayoutEntry {
+        uuid: "a".to_string(),
+        frame: Frame::new(1.0, 2.0, 3.0, 4.0),
+        is_floating: true,
+        is_collapsed: true,
+    }];
+    db.save_layout("desk", &entries, 1000).unwrap();
+
+    assert_eq!(db.get_layout("desk").unwrap(), entries);
+    assert!(db.get_layout("laptop").unwrap().is_empty());
+
+    let layouts = db.list_layouts().unwrap();
+    assert_eq!(layouts.len(), 1);
+    assert_eq!(layouts[0].name, "desk");
+    assert_eq!(layouts[0].sticky_count, 1);
+
+    assert!(db.delete_layout("desk").unwrap());
+    assert!(!db.delete_layout("desk").unwrap());
+}
+
+#[test]
+fn test_snapshot_and_restore_layout() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    write_state(
+        &stickies_path,
+        &[
+            ("A", "{{100, 100}, {250, 250}}"),
+            ("B", "{{400, 300}, {200, 150}}"),
+        ],
+    );
+    let saved = layout::snapshot(&stickies_path).unwrap();
+    assert_eq!(saved.len(), 2);
+    db.save_layout("desk", &saved, 1000).unwrap();
+
+    // Windows pile up somewhere else
+    write_state(
+        &stickies_path,
+        &[
+            ("A", "{{3000, 0}, {250, 250}}"),
+            ("B", "{{3000, 0}, {200, 150}}"),
+        ],
+    );
+
+    let entries = db.get_layout("desk").unwrap();
+    let restored = layout::restore(&db, &stickies_path, "test", &entries, None).unwrap();
+    assert_eq!(restored, 2);
+
+    let state = read_stickies_state(&stickies_path.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    assert_eq!(state["a"].frame, Frame::new(100.0, 100.0, 250.0, 250.0));
+    assert_eq!(state["b"].frame, Frame::new(400.0, 300.0, 200.0, 150.0));
+}
+
+#[test]
+fn test_restore_layout_with_clamp() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    write_state(&stickies_path, &[("A", "{{100, 100}, {250, 250}}")]);
+
+    let entries = vec![LayoutEntry {
+        uuid: "a".to_string(),
+        frame: Frame::new(3000.0, 900.0, 250.0, 250.0),
+        is_floating: false,
+        is_collapsed: true,
+    }];
+    let screen = Frame::new(0.0, 0.0, 1440.0, 900.0);
+    layout::restore(&db, &stickies_path, "test", &entries, Some(&screen)).unwrap();
+
+    let state = read_stickies_state(&stickies_path.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    assert_eq!(state["a"].frame, Frame::new(1190.0, 650.0, 250.0, 250.0));
+    assert!(state["a"].is_collapsed);
+}
+
+fn metadata(color_index: i64) -> StickyMetadata {
+    StickyMetadata::new(color_index)
+}
+
+fn overlaps(a: &Frame, b: &Frame) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+#[test]
+fn test_arrange_grid_tiles_without_overlap() {
+    let screen = Frame::new(0.0, 0.0, 1920.0, 1080.0);
+    let stickies: Vec<_> = (0..4).map(|i| (format!("s{}", i), metadata(0))).collect();
+
+    let frames = layout::arrange(&stickies, Arrangement::Grid, &screen);
+
+    assert_eq!(frames.len(), 4);
+    for (i, (_, a)) in frames.iter().enumerate() {
+        assert_eq!(a.clamped_to(&screen), *a);
+        for (_, b) in &frames[i + 1..] {
+            assert!(!overlaps(a, b), "{} overlaps {}", a, b);
+        }
+    }
+    // First sticky goes top-left, keeping its size
+    assert_eq!(frames[0].1, Frame::new(10.0, 820.0, 250.0, 250.0));
+}
+
+#[test]
+fn test_arrange_grid_shrinks_to_fit() {
+    let screen = Frame::new(0.0, 0.0, 400.0, 400.0);
+    let stickies: Vec<_> = (0..4).map(|i| (format!("s{}", i), metadata(0))).collect();
+
+    let frames = layout::arrange(&stickies, Arrangement::Grid, &screen);
+
+    for (_, frame) in &frames {
+        assert_eq!(frame.width, 180.0);
+        assert_eq!(frame.height, 180.0);
+    }
+}
+
+#[test]
+fn test_arrange_cascade_offsets_each_window() {
+    let screen = Frame::new(0.0, 0.0, 1920.0, 1080.0);
+    let stickies: Vec<_> = (0..3).map(|i| (format!("s{}", i), metadata(0))).collect();
+
+    let frames = layout::arrange(&stickies, Arrangement::Cascade, &screen);
+
+    assert_eq!(frames[0].1, Frame::new(10.0, 820.0, 250.0, 250.0));
+    assert_eq!(frames[1].1, Frame::new(34.0, 796.0, 250.0, 250.0));
+    assert_eq!(frames[2].1, Frame::new(58.0, 772.0, 250.0, 250.0));
+}
+
+#[test]
+fn test_arrange_by_color_uses_one_column_per_color() {
+    let screen = Frame::new(0.0, 0.0, 1200.0, 900.0);
+    let stickies = vec![
+        ("a".to_string(), metadata(3)),
+        ("b".to_string(), metadata(0)),
+        ("c".to_string(), metadata(3)),
+    ];
+
+    let frames = layout::arrange(&stickies, Arrangement::ByColor, &screen);
+    let x_of = |uuid: &str| frames.iter().find(|(u, _)| u == uuid).unwrap().1.x;
+
+    // Yellow (0) sorts before pink (3)
+    assert_eq!(x_of("b"), 10.0);
+    assert_eq!(x_of("a"), 610.0);
+    assert_eq!(x_of("c"), 610.0);
+}
+
+#[test]
+fn test_arrange_stickies_writes_state_file() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+    write_state(
+        dir.path(),
+        &[
+            ("AAAA", "{{900, 900}, {250, 250}}"),
+            ("BBBB", "{{900, 900}, {250, 250}}"),
+        ],
+    );
+    let screen = Frame::new(0.0, 0.0, 1920.0, 1080.0);
+
+    let only: HashSet<String> = ["aaaa".to_string()].into();
+    let moved = layout::arrange_stickies(
+        &db,
+        dir.path(),
+        "test",
+        Arrangement::Grid,
+        &screen,
+        Some(&only),
+    )
+    .unwrap();
+    assert_eq!(moved, 1);
+
+    let state = read_stickies_state(&dir.path().join(filesystem::SAVED_STATE_FILE)).unwrap();
+    assert_eq!(state["aaaa"].frame, Frame::new(10.0, 820.0, 250.0, 250.0));
+    assert_eq!(state["bbbb"].frame, Frame::new(900.0, 900.0, 250.0, 250.0));
+}
@@ -0,0 +1,156 @@
+// ABOUTME: Tests for the inter-process sync lock
+// ABOUTME: Verifies lock file contention, lease takeover and the Locked error
+
+use std::fs;
+use sticky_situation::database::{Database, SyncLease};
+use sticky_situation::sync::lock::{SyncLock, LEASE_TIMEOUT_SECS};
+use sticky_situation::sync::{runner, SyncOptions};
+use sticky_situation::StickyError;
+use tempfile::tempdir;
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// PID of a process that has already exited
+fn exited_pid() -> u32 {
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+    pid
+}
+
+#[test]
+fn test_second_lock_reports_holder() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let lock = SyncLock::acquire(&db, "laptop-id", "laptop").unwrap();
+    match SyncLock::acquire(&db, "laptop-id", "laptop") {
+        Err(StickyError::Locked { hostname, pid }) => {
+            assert_eq!(hostname, "laptop");
+            assert_eq!(pid, std::process::id());
+        }
+        other => panic!("expected Locked, got {:?}", other.map(|_| ())),
+    }
+
+    drop(lock);
+    assert!(SyncLock::acquire(&db, "laptop-id", "laptop").is_ok());
+}
+
+#[test]
+fn test_lease_held_by_other_machine_blocks_sync() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    db.acquire_lease(
+        &SyncLease {
+            machine: "desktop-id".to_string(),
+            hostname: "desktop".to_string(),
+            pid: 4242,
+            acquired_at: now(),
+        },
+        |_| false,
+    )
+    .unwrap();
+
+    let options = SyncOptions {
+        hostname: "laptop".to_string(),
+        ..Default::default()
+    };
+    let err = runner::run(&db, &stickies_path, &options).unwrap_err();
+    assert!(matches!(
+        err,
+        StickyError::Locked { ref hostname, pid: 4242 } if hostname == "desktop"
+    ));
+    assert!(err.to_string().contains("desktop"));
+}
+
+#[test]
+fn test_stale_leases_are_taken_over() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    // Another machine that stopped mid-sync long ago
+    db.acquire_lease(
+        &SyncLease {
+            machine: "desktop-id".to_string(),
+            hostname: "desktop".to_string(),
+            pid: 4242,
+            acquired_at: now() - LEASE_TIMEOUT_SECS - 60,
+        },
+        |_| false,
+    )
+    .unwrap();
+    drop(SyncLock::acquire(&db, "laptop-id", "laptop").unwrap());
+
+    // A crashed sync on this machine, which no longer holds the lock file
+    db.acquire_lease(
+        &SyncLease {
+            machine: "laptop-id".to_string(),
+            hostname: "laptop".to_string(),
+            pid: exited_pid(),
+            acquired_at: now(),
+        },
+        |_| false,
+    )
+    .unwrap();
+    assert!(SyncLock::acquire(&db, "laptop-id", "laptop").is_ok());
+}
+
+#[test]
+fn test_leases_are_keyed_on_machine_id_not_hostname() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    // Another installation with the same default hostname, mid-sync
+    db.acquire_lease(
+        &SyncLease {
+            machine: "other-id".to_string(),
+            hostname: "MacBook-Pro.local".to_string(),
+            pid: exited_pid(),
+            acquired_at: now(),
+        },
+        |_| false,
+    )
+    .unwrap();
+    assert!(matches!(
+        SyncLock::acquire(&db, "laptop-id", "MacBook-Pro.local"),
+        Err(StickyError::Locked { .. })
+    ));
+}
+
+#[test]
+fn test_live_same_machine_holder_keeps_its_lease() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    // A running sync on this machine that the lock file didn't catch
+    let mut holder = std::process::Command::new("sleep")
+        .arg("30")
+        .spawn()
+        .unwrap();
+    db.acquire_lease(
+        &SyncLease {
+            machine: "laptop-id".to_string(),
+            hostname: "laptop".to_string(),
+            pid: holder.id(),
+            acquired_at: now(),
+        },
+        |_| false,
+    )
+    .unwrap();
+    assert!(matches!(
+        SyncLock::acquire(&db, "laptop-id", "laptop"),
+        Err(StickyError::Locked { ref hostname, .. }) if hostname == "laptop"
+    ));
+
+    holder.kill().unwrap();
+    holder.wait().unwrap();
+    assert!(SyncLock::acquire(&db, "laptop-id", "laptop").is_ok());
+}
@@ -0,0 +1,222 @@
+// ABOUTME: Tests for merging one sticky-situation database into another
+// ABOUTME: Verifies added and newer stickies are copied, older ones kept, tags, layouts and dry runs
+
+use plist::{Dictionary, Value};
+use std::fs;
+use std::path::Path;
+use sticky_situation::database::{AttachmentInfo, Database, Sticky};
+use sticky_situation::filesystem::{
+    self, plist::read_stickies_state, rtfd::Attachment, Frame, RtfdBundle, StickyMetadata,
+};
+use sticky_situation::sync::{merge, runner, SyncAction, SyncOptions};
+use sticky_situation::version_vector::VersionVector;
+use tempfile::tempdir;
+
+fn sticky(uuid: &str, text: &str, modified_ns: i64) -> Sticky {
+    Sticky {
+        uuid: uuid.to_string(),
+        content_text: text.to_string(),
+        rtf_data: text.as_bytes().to_vec(),
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: modified_ns / 1_000_000_000,
+        created_at: modified_ns / 1_000_000_000,
+        source_machine: "backup".to_string(),
+        modified_ns,
+        content_hash: format!("hash of {}", text),
+        version_vector: VersionVector::new(),
+    }
+}
+
+fn options(dry_run: bool) -> SyncOptions {
+    SyncOptions {
+        dry_run,
+        hostname: "test".to_string(),
+        machine_id: "test".to_string(),
+        ..Default::default()
+    }
+}
+
+fn setup(dir: &Path) -> (Database, std::path::PathBuf) {
+    let db = Database::create(&dir.join("ours.db")).unwrap();
+    db.insert_sticky(&sticky(
+        "shared-new",
+        "Ours, newer",
+        2_000_000_000_000_000_000,
+    ))
+    .unwrap();
+    db.insert_sticky(&sticky(
+        "shared-old",
+        "Ours, older",
+        1_000_000_000_000_000_000,
+    ))
+    .unwrap();
+    db.insert_sticky(&sticky("only-ours", "Only here", 1_000_000_000_000_000_000))
+        .unwrap();
+
+    let other_path = dir.join("backup.db");
+    let other = Database::create(&other_path).unwrap();
+    other
+        .insert_sticky(&sticky(
+            "shared-new",
+            "Theirs, older",
+            1_500_000_000_000_000_000,
+        ))
+        .unwrap();
+    other
+        .insert_sticky(&sticky(
+            "shared-old",
+            "Theirs, newer",
+            1_500_000_000_000_000_000,
+        ))
+        .unwrap();
+    other
+        .insert_sticky(&sticky(
+            "only-theirs",
+            "From the backup",
+            1_000_000_000_000_000_000,
+        ))
+        .unwrap();
+    other
+        .save_attachments(
+            "only-theirs",
+            &[Attachment {
+                filename: "image.png".to_string(),
+                content: b"png".to_vec(),
+            }],
+        )
+        .unwrap();
+
+    (db, other_path)
+}
+
+#[test]
+fn test_merge_copies_new_and_newer_stickies() {
+    let dir = tempdir().unwrap();
+    let (db, other_path) = setup(dir.path());
+
+    let report = merge::merge_database(&db, &other_path, &options(false)).unwrap();
+    assert_eq!(report.changed(), 2);
+    assert!(report
+        .actions
+        .contains(&SyncAction::NewOnFilesystem("only-theirs".to_string())));
+    assert!(report
+        .actions
+        .contains(&SyncAction::UpdateDatabase("shared-old".to_string())));
+
+    let text = |uuid: &str| db.get_sticky(uuid).unwrap().unwrap().content_text;
+    assert_eq!(text("shared-new"), "Ours, newer");
+    assert_eq!(text("shared-old"), "Theirs, newer");
+    assert_eq!(text("only-ours"), "Only here");
+    assert_eq!(text("only-theirs"), "From the backup");
+    assert_eq!(db.get_attachments("only-theirs").unwrap().len(), 1);
+    assert_eq!(db.search("backup").unwrap().len(), 1);
+
+    // Merging again changes nothing
+    let report = merge::merge_database(&db, &other_path, &options(false)).unwrap();
+    assert_eq!(report.changed(), 0);
+}
+
+#[test]
+fn test_merge_dry_run_changes_nothing() {
+    let dir = tempdir().unwrap();
+    let (db, other_path) = setup(dir.path());
+
+    let report = merge::merge_database(&db, &other_path, &options(true)).unwrap();
+    assert_eq!(report.changed(), 2);
+    assert!(db.get_sticky("only-theirs").unwrap().is_none());
+}
+
+#[test]
+fn test_merge_missing_database_is_an_error() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("ours.db")).unwrap();
+    assert!(merge::merge_database(&db, &dir.path().join("nope.db"), &options(false)).is_err());
+}
+
+#[test]
+fn test_merge_keeps_local_only_attachments_and_manual_tags() {
+    let dir = tempdir().unwrap();
+    let (db, other_path) = setup(dir.path());
+    let recording = AttachmentInfo {
+        filename: "recording.mov".to_string(),
+        mime_type: "video/quicktime".to_string(),
+        size: 40_000_000,
+        stored: false,
+    };
+    db.save_attachments_with_skipped("shared-old", &[], std::slice::from_ref(&recording))
+        .unwrap();
+    db.add_tag("shared-old", "ours").unwrap();
+    let other = Database::create(&other_path).unwrap();
+    other.add_tag("shared-old", "theirs").unwrap();
+    other.add_tag("shared-new", "backup-only").unwrap();
+    drop(other);
+
+    merge::merge_database(&db, &other_path, &options(false)).unwrap();
+
+    // Replaced by their copy, but the recording on this machine is still known
+    assert_eq!(
+        db.get_sticky("shared-old").unwrap().unwrap().content_text,
+        "Theirs, newer"
+    );
+    assert_eq!(
+        db.get_attachment_info("shared-old").unwrap(),
+        vec![recording]
+    );
+    assert_eq!(db.get_tags("shared-old").unwrap(), vec!["ours", "theirs"]);
+    assert_eq!(db.get_tags("shared-new").unwrap(), vec!["backup-only"]);
+}
+
+#[test]
+fn test_merged_layout_survives_sync() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("ours.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("NOTE".into()));
+    plist::to_file_binary(
+        stickies_path.join(filesystem::SAVED_STATE_FILE),
+        &Value::Array(vec![Value::Dictionary(entry)]),
+    )
+    .unwrap();
+    RtfdBundle::create_minimal("Laid out")
+        .write(&filesystem::bundle_path(&stickies_path, "note"))
+        .unwrap();
+    runner::run(&db, &stickies_path, &options(false)).unwrap();
+
+    // A copy of the database where the window was moved
+    let other_path = dir.path().join("other.db");
+    fs::copy(dir.path().join("ours.db"), &other_path).unwrap();
+    let other = Database::create(&other_path).unwrap();
+    let moved = Frame::new(40.0, 50.0, 300.0, 200.0);
+    let sticky = other.get_sticky("note").unwrap().unwrap();
+    let mut metadata = StickyMetadata::from_bytes(&sticky.plist_metadata).unwrap();
+    metadata.set_frame(moved);
+    other.update_metadata("note", &metadata).unwrap();
+    drop(other);
+
+    let report = merge::merge_database(&db, &other_path, &options(false)).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateLayout("note".to_string())]
+    );
+
+    // The state file still holds the old window, so sync writes the merged one
+    let report = runner::run(&db, &stickies_path, &options(false)).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateFilesystemLayout("note".to_string())]
+    );
+    let state = read_stickies_state(&stickies_path.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    assert_eq!(state["note"].frame, moved);
+    let sticky = db.get_sticky("note").unwrap().unwrap();
+    assert_eq!(
+        StickyMetadata::from_bytes(&sticky.plist_metadata)
+            .unwrap()
+            .frame,
+        moved
+    );
+}
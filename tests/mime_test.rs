@@ -0,0 +1,27 @@
+use sticky_situation::mime::{detect, detect_with_name, OCTET_STREAM};
+
+#[test]
+fn test_detect_from_magic_bytes() {
+    assert_eq!(detect(b"\x89PNG\r\n\x1a\n...."), "image/png");
+    assert_eq!(detect(b"\xff\xd8\xff\xe0...."), "image/jpeg");
+    assert_eq!(detect(b"GIF89a...."), "image/gif");
+    assert_eq!(detect(b"MM\0*...."), "image/tiff");
+    assert_eq!(detect(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+    assert_eq!(detect(b"\0\0\0\x18ftypheic...."), "image/heic");
+    assert_eq!(detect(b"\0\0\0\x14ftypqt  ...."), "video/quicktime");
+    assert_eq!(detect(b"\0\0\0\x18ftypmp42...."), "video/mp4");
+    assert_eq!(detect(b"%PDF-1.7"), "application/pdf");
+    assert_eq!(detect(b"plain bytes"), OCTET_STREAM);
+    assert_eq!(detect(b""), OCTET_STREAM);
+}
+
+#[test]
+fn test_magic_bytes_win_over_extension() {
+    // A JPEG saved with the wrong extension is still a JPEG
+    assert_eq!(
+        detect_with_name(b"\xff\xd8\xff\xe0", "photo.png"),
+        "image/jpeg"
+    );
+    assert_eq!(detect_with_name(b"unknown", "Photo.PNG"), "image/png");
+    assert_eq!(detect_with_name(b"unknown", "notes"), OCTET_STREAM);
+}
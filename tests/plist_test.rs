@@ -1,5 +1,9 @@
 use plist::{Dictionary, Value};
-use sticky_situation::filesystem::plist::{read_stickies_state, StickyMetadata};
+use std::collections::HashMap;
+use sticky_situation::filesystem::frame::Frame;
+use sticky_situation::filesystem::plist::{
+    read_stickies_state, update_stickies_state, StickyMetadata,
+};
 use tempfile::tempdir;
 
 #[test]
@@ -13,7 +17,44 @@ fn test_parse_sticky_metadata() {
 
     let metadata = StickyMetadata::from_plist_dict(&dict).unwrap();
     assert_eq!(metadata.color_index, 0);
-    assert_eq!(metadata.frame, "{{100, 200}, {300, 400}}");
+    assert_eq!(metadata.frame, Frame::new(100.0, 200.0, 300.0, 400.0));
+    assert_eq!(metadata.frame.to_string(), "{{100, 200}, {300, 400}}");
+}
+
+#[test]
+fn test_parse_window_states() {
+    let mut dict = Dictionary::new();
+    dict.insert(
+        "Frame".to_string(),
+        Value::String("{{100, 200}, {300, 20}}".into()),
+    );
+    dict.insert(
+        "ExpandedFrame".to_string(),
+        Value::String("{{100, 200}, {300, 400}}".into()),
+    );
+    dict.insert("Collapsed".to_string(), Value::Boolean(true));
+    dict.insert("Translucent".to_string(), Value::Boolean(true));
+    dict.insert("CustomColor".to_string(), Value::String("#A0C4FF".into()));
+    dict.insert("FontName".to_string(), Value::String("Helvetica".into()));
+    dict.insert("FontSize".to_string(), Value::Integer(14.into()));
+
+    let metadata = StickyMetadata::from_plist_dict(&dict).unwrap();
+    assert!(metadata.is_collapsed);
+    assert!(metadata.is_translucent);
+    assert_eq!(
+        metadata.expanded_frame,
+        Some(Frame::new(100.0, 200.0, 300.0, 400.0))
+    );
+    assert_eq!(metadata.custom_color.as_deref(), Some("#A0C4FF"));
+    assert_eq!(metadata.font_name.as_deref(), Some("Helvetica"));
+    assert_eq!(metadata.font_size, Some(14.0));
+
+    let defaults = StickyMetadata::from_plist_dict(&Dictionary::new()).unwrap();
+    assert!(!defaults.is_collapsed);
+    assert!(!defaults.is_translucent);
+    assert_eq!(defaults.expanded_frame, None);
+    assert_eq!(defaults.font_size, None);
+    assert_ne!(metadata.window_state(), defaults.window_state());
 }
 
 #[test]
@@ -39,7 +80,8 @@ fn test_parse_metadata_with_missing_frame() {
 
     // Should use default frame
     let metadata = StickyMetadata::from_plist_dict(&dict).unwrap();
-    assert_eq!(metadata.frame, "{{100, 100}, {250, 250}}");
+    assert_eq!(metadata.frame, Frame::default());
+    assert_eq!(metadata.frame.to_string(), "{{100, 100}, {250, 250}}");
     assert!(metadata.is_floating);
 }
 
@@ -66,7 +108,7 @@ fn test_color_name_mapping() {
         (3, "pink"),
         (4, "purple"),
         (5, "gray"),
-        (99, "yellow"), // Unknown defaults to yellow
+        (99, "color-99"), // Unknown indices are preserved
     ];
 
     for (index, expected_name) in colors {
@@ -226,3 +268,77 @@ fn test_read_empty_dictionary_plist() {
     let result = read_stickies_state(&plist_path).unwrap();
     assert_eq!(result.len(), 0);
 }
+
+#[test]
+fn test_metadata_bytes_roundtrip_preserves_unknown_keys() {
+    let mut dict = Dictionary::new();
+    dict.insert("Color".to_string(), Value::Integer(3.into()));
+    dict.insert(
+        "Frame".to_string(),
+        Value::String("{{10, 20}, {300, 200}}".into()),
+    );
+    dict.insert("Floating".to_string(), Value::Boolean(true));
+    dict.insert("SomeFutureKey".to_string(), Value::String("keep me".into()));
+
+    let metadata = StickyMetadata::from_plist_dict(&dict).unwrap();
+    let bytes = metadata.to_bytes().unwrap();
+    let restored = StickyMetadata::from_bytes(&bytes).unwrap();
+
+    assert_eq!(restored.color_index, 3);
+    assert_eq!(restored.frame, Frame::new(10.0, 20.0, 300.0, 200.0));
+    assert!(restored.is_floating);
+    assert_eq!(restored.raw, dict);
+}
+
+#[test]
+fn test_update_stickies_state_replaces_and_appends() {
+    let dir = tempdir().unwrap();
+    let plist_path = dir.path().join(".SavedStickiesState");
+
+    let mut keep = Dictionary::new();
+    keep.insert("UUID".to_string(), Value::String("KEEP-1".into()));
+    keep.insert("Color".to_string(), Value::Integer(0.into()));
+
+    let mut existing = Dictionary::new();
+    existing.insert("UUID".to_string(), Value::String("ABC-123".into()));
+    existing.insert("Color".to_string(), Value::Integer(0.into()));
+
+    plist::to_file_binary(
+        &plist_path,
+        &Value::Array(vec![Value::Dictionary(keep), Value::Dictionary(existing)]),
+    )
+    .unwrap();
+
+    let mut replacement = Dictionary::new();
+    replacement.insert("Color".to_string(), Value::Integer(4.into()));
+    replacement.insert("Unknown".to_string(), Value::Boolean(true));
+
+    let mut added = Dictionary::new();
+    added.insert("Color".to_string(), Value::Integer(2.into()));
+
+    let updates = HashMap::from([
+        ("abc-123".to_string(), replacement),
+        ("new-456".to_string(), added),
+    ]);
+    update_stickies_state(&plist_path, &updates).unwrap();
+
+    let result = read_stickies_state(&plist_path).unwrap();
+    assert_eq!(result.len(), 3);
+    assert_eq!(result["keep-1"].color_index, 0);
+    assert_eq!(result["abc-123"].color_index, 4);
+    assert_eq!(
+        result["abc-123"]
+            .raw
+            .get("UUID")
+            .and_then(|v| v.as_string()),
+        Some("ABC-123")
+    );
+    assert_eq!(
+        result["abc-123"].raw.get("Unknown"),
+        Some(&Value::Boolean(true))
+    );
+    assert_eq!(result["new-456"].color_index, 2);
+
+    // The file stays in binary format
+    assert!(std::fs::read(&plist_path).unwrap().starts_with(b"bplist"));
+}
@@ -0,0 +1,250 @@
+use sticky_situation::database::{Database, Sticky};
+use sticky_situation::filesystem::StickyColor;
+use sticky_situation::query::{
+    escape_fts, Comparison, Filter, HasFilter, IsFilter, SearchQuery, Term,
+};
+use sticky_situation::version_vector::VersionVector;
+use sticky_situation::StickyError;
+use tempfile::tempdir;
+
+#[test]
+fn test_parse_plain_words_and_phrase() {
+    let query = SearchQuery::parse(r#"meeting "exact phrase" notes"#).unwrap();
+    assert_eq!(
+        query.terms,
+        vec![
+            Term::Word("meeting".to_string()),
+            Term::Phrase("exact phrase".to_string()),
+            Term::Word("notes".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_filters() {
+    let query = SearchQuery::parse("color:Pink machine:work-laptop has:image is:floating").unwrap();
+    assert_eq!(
+        query.terms,
+        vec![
+            Term::Filter(Filter::Color(StickyColor::Pink)),
+            Term::Filter(Filter::Machine("work-laptop".to_string())),
+            Term::Filter(Filter::Has(HasFilter::Image)),
+            Term::Filter(Filter::Is(IsFilter::Floating)),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_date_filter() {
+    let query = SearchQuery::parse("modified:>2026-09-01").unwrap();
+    match &query.terms[0] {
+        Term::Filter(Filter::Modified(date)) => {
+            assert_eq!(date.comparison, Comparison::After);
+            // 2026-09-01T00:00:00Z
+            assert_eq!(date.day_start, 1_788_220_800);
+        }
+        other => panic!("unexpected term: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_negation() {
+    let query = SearchQuery::parse(r#"-excluded -"bad phrase" -color:gray"#).unwrap();
+    assert_eq!(
+        query.terms,
+        vec![
+            Term::Not(Box::new(Term::Word("excluded".to_string()))),
+            Term::Not(Box::new(Term::Phrase("bad phrase".to_string()))),
+            Term::Not(Box::new(Term::Filter(Filter::Color(StickyColor::Gray)))),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_unknown_key_is_text() {
+    let query = SearchQuery::parse("note:thing").unwrap();
+    assert_eq!(query.terms, vec![Term::Word("note:thing".to_string())]);
+}
+
+#[test]
+fn test_parse_invalid_filter_values() {
+    assert!(SearchQuery::parse("modified:>yesterday").is_err());
+    assert!(SearchQuery::parse("has:video").is_err());
+    assert!(SearchQuery::parse("is:sideways").is_err());
+}
+
+#[test]
+fn test_compile_uses_parameters() {
+    let query = SearchQuery::parse("budget color:pink -draft").unwrap();
+    let compiled = query.compile();
+
+    assert_eq!(compiled.fts_match.as_deref(), Some("\"budget\""));
+    assert_eq!(compiled.conditions.len(), 2);
+    assert_eq!(compiled.params.len(), 2);
+    assert!(compiled.conditions.iter().all(|c| !c.contains("pink")));
+}
+
+#[test]
+fn test_search_query_against_database() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let entries = [
+        (
+            "a",
+            "Budget review draft",
+            "pink",
+            "work-laptop",
+            1_788_400_000,
+            true,
+        ),
+        (
+            "b",
+            "Budget review final",
+            "pink",
+            "home",
+            1_788_400_000,
+            false,
+        ),
+        (
+            "c",
+            "Budget for groceries",
+            "blue",
+            "work-laptop",
+            1_700_000_000,
+            false,
+        ),
+    ];
+
+    for (uuid, content, color, machine, modified_at, is_floating) in entries {
+        db.insert_sticky(&Sticky {
+            uuid: uuid.to_string(),
+            content_text: content.to_string(),
+            rtf_data: vec![],
+            plist_metadata: vec![],
+            is_floating,
+            color: color.to_string(),
+            modified_at,
+            created_at: modified_at,
+            source_machine: machine.to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
+        })
+        .unwrap();
+    }
+
+    let uuids = |q: &str| -> Vec<String> {
+        let mut uuids: Vec<String> = db
+            .search_query(&SearchQuery::parse(q).unwrap(), None, 0)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.sticky.uuid)
+            .collect();
+        uuids.sort();
+        uuids
+    };
+
+    assert_eq!(uuids("budget color:pink"), vec!["a", "b"]);
+    assert_eq!(uuids("budget color:pink -draft"), vec!["b"]);
+    assert_eq!(uuids("machine:WORK-LAPTOP"), vec!["a", "c"]);
+    assert_eq!(uuids("modified:>2026-09-01"), vec!["a", "b"]);
+    assert_eq!(uuids("modified:<2026-09-01 budget"), vec!["c"]);
+    assert_eq!(uuids("is:floating"), vec!["a"]);
+    assert_eq!(uuids(r#""review final""#), vec!["b"]);
+    assert_eq!(uuids("-color:pink"), vec!["c"]);
+}
+
+#[test]
+fn test_escape_fts_quotes_each_word() {
+    assert_eq!(escape_fts("foo-bar c++"), r#""foo-bar" "c++""#);
+    assert_eq!(escape_fts(r#"say "hi""#), r#""say" """hi""""#);
+    assert_eq!(escape_fts("plan*"), r#""plan"*"#);
+}
+
+#[test]
+fn test_literal_search_handles_special_characters() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    db.insert_sticky(&Sticky {
+        uuid: "special".to_string(),
+        content_text: "Learning c++ and foo-bar syntax".to_string(),
+        rtf_data: vec![],
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1000,
+        created_at: 1000,
+        source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+
+    for query in ["foo-bar", "c++", r#""unbalanced"#, "AND", "syntax:"] {
+        let parsed = SearchQuery::parse(query).unwrap();
+        assert!(db.search_query(&parsed, None, 0).is_ok(), "query {}", query);
+        assert!(db.search(query).is_ok(), "query {}", query);
+    }
+
+    assert_eq!(db.search("foo-bar").unwrap().len(), 1);
+    assert_eq!(db.search("c++").unwrap().len(), 1);
+}
+
+#[test]
+fn test_raw_fts_mode() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    for (uuid, content) in [("a", "apples"), ("b", "bananas"), ("c", "cherries")] {
+        db.insert_sticky(&Sticky {
+            uuid: uuid.to_string(),
+            content_text: content.to_string(),
+            rtf_data: vec![],
+            plist_metadata: vec![],
+            is_floating: false,
+            color: "yellow".to_string(),
+            modified_at: 1000,
+            created_at: 1000,
+            source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
+        })
+        .unwrap();
+    }
+
+    let hits = db
+        .search_query(&SearchQuery::raw("apples OR bananas"), None, 0)
+        .unwrap();
+    assert_eq!(hits.len(), 2);
+}
+
+#[test]
+fn test_raw_fts_syntax_error_is_invalid_query() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    for query in ["c++", r#""unbalanced"#, "foo-bar", "AND"] {
+        let result = db.search_query(&SearchQuery::raw(query), None, 0);
+        assert!(
+            matches!(result, Err(StickyError::InvalidQuery { .. })),
+            "query {} gave {:?}",
+            query,
+            result.map(|hits| hits.len())
+        );
+    }
+}
+
+#[test]
+fn test_only_raw_queries_are_marked_raw() {
+    assert!(SearchQuery::raw("budget:2026").compile().raw);
+    assert!(
+        !SearchQuery::parse("budget -draft color:pink")
+            .unwrap()
+            .compile()
+            .raw
+    );
+}
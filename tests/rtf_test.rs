@@ -1,4 +1,4 @@
-use sticky_situation::rtf::extract_text;
+use sticky_situation::rtf::{attachment_references, extract_text};
 
 #[test]
 fn test_extract_plain_text() {
@@ -34,3 +34,17 @@ fn test_display_extracted_text() {
     let text2 = extract_text(rtf2);
     println!("Extracted from formatted RTF: '{}'", text2);
 }
+
+#[test]
+fn test_attachment_references() {
+    let rtf = r"{\rtf1\ansi {{\NeXTGraphic Pasted Graphic.tiff \width2560 \height1600 \appleattachmentpadding0
+}}Some text{{\NeXTGraphic image.png \width32 \height32
+}}{\attachment notes.pdf}{{\NeXTGraphic image.png \width32 \height32
+}}}";
+
+    assert_eq!(
+        attachment_references(rtf),
+        vec!["Pasted Graphic.tiff", "image.png", "notes.pdf"]
+    );
+    assert!(attachment_references(r"{\rtf1 plain text}").is_empty());
+}
@@ -166,24 +166,74 @@ fn test_read_rtfd_large_rtf_content() {
 }
 
 #[test]
-fn test_read_rtfd_includes_hidden_files() {
+fn test_read_rtfd_skips_junk_files() {
     let dir = tempdir().unwrap();
     let rtfd_dir = dir.path().join("test.rtfd");
     fs::create_dir(&rtfd_dir).unwrap();
 
     fs::write(rtfd_dir.join("TXT.rtf"), "test").unwrap();
     fs::write(rtfd_dir.join(".DS_Store"), b"mac metadata").unwrap();
+    fs::write(rtfd_dir.join("._image.png"), b"appledouble").unwrap();
     fs::write(rtfd_dir.join(".hidden"), b"hidden file").unwrap();
 
     let bundle = RtfdBundle::read(&rtfd_dir).unwrap();
-    // Current implementation includes hidden files as attachments
-    assert_eq!(bundle.attachments.len(), 2);
 
-    let filenames: Vec<String> = bundle
+    // Finder and AppleDouble files are ignored; other hidden files are kept
+    let filenames: Vec<&str> = bundle
         .attachments
         .iter()
-        .map(|a| a.filename.clone())
+        .map(|a| a.filename.as_str())
         .collect();
-    assert!(filenames.contains(&".DS_Store".to_string()));
-    assert!(filenames.contains(&".hidden".to_string()));
+    assert_eq!(filenames, vec![".hidden"]);
+}
+
+#[test]
+fn test_read_rtfd_nested_attachment_directories() {
+    let dir = tempdir().unwrap();
+    let rtfd_dir = dir.path().join("test.rtfd");
+    fs::create_dir_all(rtfd_dir.join("Report.pages/Data")).unwrap();
+
+    fs::write(rtfd_dir.join("TXT.rtf"), "test").unwrap();
+    fs::write(rtfd_dir.join("Report.pages/Index.zip"), b"index").unwrap();
+    fs::write(rtfd_dir.join("Report.pages/Data/preview.jpg"), b"jpg").unwrap();
+    fs::write(rtfd_dir.join("Report.pages/.DS_Store"), b"junk").unwrap();
+
+    let bundle = RtfdBundle::read(&rtfd_dir).unwrap();
+    let filenames: Vec<&str> = bundle
+        .attachments
+        .iter()
+        .map(|a| a.filename.as_str())
+        .collect();
+    assert_eq!(
+        filenames,
+        vec!["Report.pages/Data/preview.jpg", "Report.pages/Index.zip"]
+    );
+
+    // Nested attachments are written back to the same place
+    let copy = dir.path().join("copy.rtfd");
+    bundle.write(&copy).unwrap();
+    assert_eq!(
+        fs::read(copy.join("Report.pages/Data/preview.jpg")).unwrap(),
+        b"jpg"
+    );
+}
+
+#[test]
+fn test_check_attachments_reports_orphaned_and_missing() {
+    let dir = tempdir().unwrap();
+    let rtfd_dir = dir.path().join("test.rtfd");
+    fs::create_dir_all(rtfd_dir.join("Report.pages")).unwrap();
+
+    let rtf = "{\\rtf1 {{\\NeXTGraphic Pasted Graphic.tiff \\width640 \\height480\n}}\n\
+               {{\\NeXTGraphic Report.pages \\width32 \\height32\n}}\n\
+               {{\\NeXTGraphic gone.png \\width32 \\height32\n}}}";
+    fs::write(rtfd_dir.join("TXT.rtf"), rtf).unwrap();
+    fs::write(rtfd_dir.join("Pasted Graphic.tiff"), b"tiff").unwrap();
+    fs::write(rtfd_dir.join("Report.pages/Index.zip"), b"index").unwrap();
+    fs::write(rtfd_dir.join("stray.png"), b"png").unwrap();
+
+    let check = RtfdBundle::read(&rtfd_dir).unwrap().check_attachments();
+    assert_eq!(check.missing, vec!["gone.png"]);
+    assert_eq!(check.orphaned, vec!["stray.png"]);
+    assert!(!check.is_ok());
 }
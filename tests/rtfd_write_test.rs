@@ -38,3 +38,151 @@ fn test_write_rtfd_with_attachments() {
 
     assert!(rtfd_path.join("IMG_001.tiff").exists());
 }
+
+#[test]
+fn test_rewrite_removes_stale_attachments() {
+    let dir = tempdir().unwrap();
+    let rtfd_path = dir.path().join("test.rtfd");
+
+    let with_image = RtfdBundle {
+        rtf_data: b"{\\rtf1 Old}".to_vec(),
+        attachments: vec![Attachment {
+            filename: "IMG_001.tiff".to_string(),
+            content: b"fake image".to_vec(),
+        }],
+    };
+    with_image.write(&rtfd_path).unwrap();
+
+    RtfdBundle::create_minimal("New").write(&rtfd_path).unwrap();
+
+    assert!(!rtfd_path.join("IMG_001.tiff").exists());
+    let bundle = RtfdBundle::read(&rtfd_path).unwrap();
+    assert!(bundle.attachments.is_empty());
+    assert!(String::from_utf8_lossy(&bundle.rtf_data).contains("New"));
+
+    // Only the bundle itself is left behind, no temporary directories
+    let entries: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn test_invalid_attachment_leaves_existing_bundle_untouched() {
+    let dir = tempdir().unwrap();
+    let rtfd_path = dir.path().join("test.rtfd");
+    RtfdBundle::create_minimal("Original")
+        .write(&rtfd_path)
+        .unwrap();
+
+    let bad = RtfdBundle {
+        rtf_data: b"{\\rtf1 Replacement}".to_vec(),
+        attachments: vec![Attachment {
+            filename: "../escape.png".to_string(),
+            content: b"data".to_vec(),
+        }],
+    };
+    assert!(bad.write(&rtfd_path).is_err());
+
+    let content = fs::read_to_string(rtfd_path.join("TXT.rtf")).unwrap();
+    assert!(content.contains("Original"));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_content_hash_covers_rtf_and_attachments() {
+    let image = |name: &str, content: &[u8]| Attachment {
+        filename: name.to_string(),
+        content: content.to_vec(),
+    };
+    let bundle = RtfdBundle {
+        rtf_data: b"{\\rtf1 Test}".to_vec(),
+        attachments: vec![image("a.png", b"one"), image("b.png", b"two")],
+    };
+
+    let reordered = RtfdBundle {
+        rtf_data: bundle.rtf_data.clone(),
+        attachments: vec![image("b.png", b"two"), image("a.png", b"one")],
+    };
+    assert_eq!(bundle.content_hash(), reordered.content_hash());
+    assert_eq!(bundle.content_hash().len(), 64);
+
+    let changed = RtfdBundle {
+        rtf_data: bundle.rtf_data.clone(),
+        attachments: vec![image("a.png", b"one"), image("b.png", b"2")],
+    };
+    assert_ne!(bundle.content_hash(), changed.content_hash());
+}
+
+#[test]
+fn test_modified_time_ns_roundtrip() {
+    let dir = tempdir().unwrap();
+    let rtfd_path = dir.path().join("test.rtfd");
+    RtfdBundle::create_minimal("Time")
+        .write(&rtfd_path)
+        .unwrap();
+
+    RtfdBundle::set_modified_time_ns(&rtfd_path, 1_700_000_000_123_456_789).unwrap();
+
+    assert_eq!(
+        RtfdBundle::modified_time_ns(&rtfd_path).unwrap(),
+        1_700_000_000_123_456_789
+    );
+    assert_eq!(
+        RtfdBundle::modified_time(&rtfd_path).unwrap(),
+        1_700_000_000
+    );
+}
+
+#[test]
+fn test_write_rejects_traversal_in_nested_paths() {
+    let dir = tempdir().unwrap();
+    let rtfd_path = dir.path().join("test.rtfd");
+
+    for filename in [
+        "Folder/../../escape.png",
+        "Folder//image.png",
+        "Folder\\image.png",
+    ] {
+        let bundle = RtfdBundle {
+            rtf_data: b"{\\rtf1 Test}".to_vec(),
+            attachments: vec![Attachment {
+                filename: filename.to_string(),
+                content: b"data".to_vec(),
+            }],
+        };
+        assert!(bundle.write(&rtfd_path).is_err(), "{}", filename);
+    }
+}
+
+#[test]
+fn test_interrupted_write_is_recovered() {
+    let dir = tempdir().unwrap();
+    let rtfd_path = dir.path().join("note.rtfd");
+    let bundle = RtfdBundle {
+        rtf_data: b"{\\rtf1 Previous}".to_vec(),
+        attachments: vec![],
+    };
+
+    // A writer that died after moving the old bundle aside, and one that
+    // died while staging a different note
+    bundle
+        .write(&dir.path().join(".note.rtfd.old-99999999"))
+        .unwrap();
+    bundle
+        .write(&dir.path().join(".note.rtfd.tmp-99999999"))
+        .unwrap();
+    bundle
+        .write(&dir.path().join(".other.rtfd.tmp-99999999"))
+        .unwrap();
+
+    RtfdBundle::recover_interrupted_writes(dir.path()).unwrap();
+
+    assert_eq!(
+        RtfdBundle::read(&rtfd_path).unwrap().rtf_data,
+        b"{\\rtf1 Previous}"
+    );
+    let names: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, vec!["note.rtfd"]);
+}
@@ -0,0 +1,385 @@
+// ABOUTME: Tests for shared mode, where machines sync through per-machine change logs
+// ABOUTME: Verifies publishing, merging, conflict ordering and partially synced logs
+
+use plist::{Dictionary, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use sticky_situation::database::Database;
+use sticky_situation::filesystem::{
+    self, plist::read_stickies_state, rtfd::Attachment, Frame, RtfdBundle, StickyMetadata,
+};
+use sticky_situation::layout;
+use sticky_situation::sync::{runner, shared, SyncAction, SyncOptions};
+use tempfile::tempdir;
+
+struct Machine {
+    name: String,
+    stickies: PathBuf,
+    db: Database,
+}
+
+impl Machine {
+    fn new(root: &Path, name: &str) -> Self {
+        let stickies = root.join(name).join("Stickies");
+        fs::create_dir_all(&stickies).unwrap();
+        write_state(&stickies, &[]);
+        let db = Database::create(&root.join(name).join("private.db")).unwrap();
+        Self {
+            name: name.to_string(),
+            stickies,
+            db,
+        }
+    }
+
+    fn sync(&self, shared_dir: &Path) -> runner::SyncReport {
+        let options = SyncOptions {
+            hostname: self.name.clone(),
+            machine_id: self.name.clone(),
+            shared_dir: Some(shared_dir.to_path_buf()),
+            ..Default::default()
+        };
+        runner::run(&self.db, &self.stickies, &options).unwrap()
+    }
+
+    fn write_note(&self, uuid: &str, text: &str, modified_ns: i64) {
+        let path = filesystem::bundle_path(&self.stickies, uuid);
+        RtfdBundle::create_minimal(text).write(&path).unwrap();
+        RtfdBundle::set_modified_time_ns(&path, modified_ns).unwrap();
+    }
+}
+
+fn write_state(stickies_path: &Path, uuids: &[&str]) {
+    let array = uuids
+        .iter()
+        .map(|uuid| {
+            let mut entry = Dictionary::new();
+            entry.insert("UUID".to_string(), Value::String(uuid.to_uppercase()));
+            Value::Dictionary(entry)
+        })
+        .collect();
+    plist::to_file_binary(
+        stickies_path.join(filesystem::SAVED_STATE_FILE),
+        &Value::Array(array),
+    )
+    .unwrap();
+}
+
+fn log_lines(shared_dir: &Path, machine: &str) -> usize {
+    fs::read_to_string(shared::log_path(shared_dir, machine))
+        .map(|log| log.lines().count())
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_changes_travel_through_logs() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Written on the laptop", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+    assert_eq!(log_lines(&shared_dir, "laptop"), 1);
+
+    // The desktop merges the note into its own database and writes it out
+    desktop.sync(&shared_dir);
+    let copy = RtfdBundle::read(&filesystem::bundle_path(&desktop.stickies, "note")).unwrap();
+    assert!(String::from_utf8_lossy(&copy.rtf_data).contains("Written on the laptop"));
+    // ...without echoing it back into its own log
+    assert_eq!(log_lines(&shared_dir, "desktop"), 0);
+    desktop.sync(&shared_dir);
+    assert_eq!(log_lines(&shared_dir, "desktop"), 0);
+
+    // An edit on the desktop comes back to the laptop
+    desktop.write_note("note", "Edited on the desktop", 1_700_000_100_000_000_000);
+    desktop.sync(&shared_dir);
+    assert_eq!(log_lines(&shared_dir, "desktop"), 1);
+    laptop.sync(&shared_dir);
+    let note = laptop.db.get_sticky("note").unwrap().unwrap();
+    assert!(note.content_text.contains("Edited on the desktop"));
+    let bundle = RtfdBundle::read(&filesystem::bundle_path(&laptop.stickies, "note")).unwrap();
+    assert!(String::from_utf8_lossy(&bundle.rtf_data).contains("Edited on the desktop"));
+    assert_eq!(log_lines(&shared_dir, "laptop"), 1);
+}
+
+#[test]
+fn test_older_remote_change_loses() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Original", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+
+    // Both edit; the desktop's edit is newer
+    laptop.write_note("note", "Older edit", 1_700_000_100_000_000_000);
+    desktop.write_note("note", "Newer edit", 1_700_000_200_000_000_000);
+    laptop.sync(&shared_dir);
+    let report = desktop.sync(&shared_dir);
+    assert_eq!(report.conflicts.len(), 1);
+    laptop.sync(&shared_dir);
+
+    for machine in [&laptop, &desktop] {
+        let note = machine.db.get_sticky("note").unwrap().unwrap();
+        assert!(note.content_text.contains("Newer edit"), "{}", machine.name);
+    }
+}
+
+#[test]
+fn test_later_edit_wins_despite_clock_skew() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Original", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+
+    // The desktop edits after seeing the note, but its clock is behind
+    desktop.write_note("note", "Edited later", 1_699_999_000_000_000_000);
+    desktop.sync(&shared_dir);
+    let report = laptop.sync(&shared_dir);
+    assert!(report.conflicts.is_empty());
+
+    let note = laptop.db.get_sticky("note").unwrap().unwrap();
+    assert!(note.content_text.contains("Edited later"));
+    let bundle = RtfdBundle::read(&filesystem::bundle_path(&laptop.stickies, "note")).unwrap();
+    assert!(String::from_utf8_lossy(&bundle.rtf_data).contains("Edited later"));
+}
+
+#[test]
+fn test_entry_waits_for_its_blobs() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Arrives in pieces", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+
+    // The log line has synced to the desktop but the content hasn't
+    let blobs = shared_dir.join(shared::BLOBS_DIR);
+    let parked = dir.path().join("parked");
+    fs::rename(&blobs, &parked).unwrap();
+    desktop.sync(&shared_dir);
+    assert!(desktop.db.get_sticky("note").unwrap().is_none());
+
+    fs::rename(&parked, &blobs).unwrap();
+    desktop.sync(&shared_dir);
+    assert!(desktop.db.get_sticky("note").unwrap().is_some());
+}
+
+#[test]
+fn test_unreadable_log_lines_are_skipped() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    let log = shared::log_path(&shared_dir, "laptop");
+    fs::create_dir_all(log.parent().unwrap()).unwrap();
+    fs::write(&log, "not json\n{\"partial\": ").unwrap();
+
+    let report = desktop.sync(&shared_dir);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("laptop.ndjson"));
+
+    // The half-written last line is not skipped past
+    let report = desktop.sync(&shared_dir);
+    assert!(report.warnings.is_empty());
+}
+
+fn frame_in(machine: &Machine) -> (Frame, Frame) {
+    let state = read_stickies_state(&machine.stickies.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    let sticky = machine.db.get_sticky("note").unwrap().unwrap();
+    let stored = StickyMetadata::from_bytes(&sticky.plist_metadata).unwrap();
+    (state["note"].frame, stored.frame)
+}
+
+#[test]
+fn test_window_moves_settle_without_growing_logs() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Moved around", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+
+    // Moved on the laptop; the desktop takes it into its state file
+    let moved = Frame::new(40.0, 50.0, 300.0, 200.0);
+    layout::set_frame(
+        &laptop.db,
+        Some(&laptop.stickies),
+        "laptop",
+        "note",
+        Some((300.0, 200.0)),
+        Some((40.0, 50.0)),
+    )
+    .unwrap();
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+    assert_eq!(frame_in(&desktop), (moved, moved));
+
+    // Moved on both before either saw the other's move
+    for (machine, x) in [(&laptop, 100.0), (&desktop, 200.0)] {
+        layout::set_frame(
+            &machine.db,
+            Some(&machine.stickies),
+            &machine.name,
+            "note",
+            None,
+            Some((x, 0.0)),
+        )
+        .unwrap();
+        machine.sync(&shared_dir);
+    }
+
+    // Each takes in the other's move once, then the logs stop growing
+    let log_sizes = || {
+        (
+            log_lines(&shared_dir, "laptop"),
+            log_lines(&shared_dir, "desktop"),
+        )
+    };
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+    let settled = log_sizes();
+    for _ in 0..3 {
+        laptop.sync(&shared_dir);
+        desktop.sync(&shared_dir);
+        assert_eq!(log_sizes(), settled);
+    }
+
+    // Both settle on the same window
+    let (state, stored) = frame_in(&laptop);
+    assert_eq!(state, stored);
+    assert_eq!(frame_in(&desktop), (state, stored));
+}
+
+#[test]
+fn test_dry_run_shows_remote_changes_without_merging_them() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Not here yet", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+
+    let options = SyncOptions {
+        dry_run: true,
+        hostname: desktop.name.clone(),
+        machine_id: desktop.name.clone(),
+        shared_dir: Some(shared_dir.clone()),
+        ..Default::default()
+    };
+    let report = runner::run(&desktop.db, &desktop.stickies, &options).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::NewInDatabase("note".to_string())]
+    );
+    assert!(desktop.db.get_sticky("note").unwrap().is_none());
+    assert!(!filesystem::bundle_path(&desktop.stickies, "note").exists());
+
+    // The real sync still merges the entry the dry run saw
+    desktop.sync(&shared_dir);
+    assert!(desktop.db.get_sticky("note").unwrap().is_some());
+}
+
+#[test]
+fn test_remote_edit_keeps_local_only_attachments() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+    let limited = SyncOptions {
+        hostname: laptop.name.clone(),
+        machine_id: laptop.name.clone(),
+        shared_dir: Some(shared_dir.clone()),
+        max_attachment_bytes: 1024,
+        ..Default::default()
+    };
+
+    write_state(&laptop.stickies, &["note"]);
+    let recording = vec![0u8; 4096];
+    let path = filesystem::bundle_path(&laptop.stickies, "note");
+    RtfdBundle {
+        rtf_data: b"{\\rtf1 With a recording}".to_vec(),
+        attachments: vec![Attachment {
+            filename: "recording.mov".to_string(),
+            content: recording.clone(),
+        }],
+    }
+    .write(&path)
+    .unwrap();
+    RtfdBundle::set_modified_time_ns(&path, 1_700_000_000_000_000_000).unwrap();
+    runner::run(&laptop.db, &laptop.stickies, &limited).unwrap();
+    desktop.sync(&shared_dir);
+
+    // Edited on the desktop, which never had the recording
+    desktop.write_note("note", "Edited on the desktop", 1_700_000_100_000_000_000);
+    desktop.sync(&shared_dir);
+    runner::run(&laptop.db, &laptop.stickies, &limited).unwrap();
+
+    let bundle = RtfdBundle::read(&path).unwrap();
+    assert!(String::from_utf8_lossy(&bundle.rtf_data).contains("Edited on the desktop"));
+    assert_eq!(bundle.attachments.len(), 1);
+    assert_eq!(bundle.attachments[0].content, recording);
+}
+
+#[test]
+fn test_manual_tags_travel_through_logs() {
+    let dir = tempdir().unwrap();
+    let shared_dir = dir.path().join("iCloud");
+    let laptop = Machine::new(dir.path(), "laptop");
+    let desktop = Machine::new(dir.path(), "desktop");
+
+    write_state(&laptop.stickies, &["note"]);
+    laptop.write_note("note", "Quarterly numbers", 1_700_000_000_000_000_000);
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+
+    // A tag alone is a change worth publishing
+    laptop.db.add_tag("note", "work").unwrap();
+    laptop.sync(&shared_dir);
+    assert_eq!(log_lines(&shared_dir, "laptop"), 2);
+    desktop.sync(&shared_dir);
+    assert_eq!(desktop.db.get_manual_tags("note").unwrap(), vec!["work"]);
+    assert_eq!(log_lines(&shared_dir, "desktop"), 0);
+
+    // Tags added on both machines end up on both
+    desktop.db.add_tag("note", "finance").unwrap();
+    desktop.sync(&shared_dir);
+    laptop.sync(&shared_dir);
+    for machine in [&laptop, &desktop] {
+        assert_eq!(
+            machine.db.get_manual_tags("note").unwrap(),
+            vec!["finance", "work"]
+        );
+    }
+
+    // ...and settle without more log entries
+    let lines = (
+        log_lines(&shared_dir, "laptop"),
+        log_lines(&shared_dir, "desktop"),
+    );
+    laptop.sync(&shared_dir);
+    desktop.sync(&shared_dir);
+    assert_eq!(
+        (
+            log_lines(&shared_dir, "laptop"),
+            log_lines(&shared_dir, "desktop")
+        ),
+        lines
+    );
+}
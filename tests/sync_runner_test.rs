@@ -0,0 +1,908 @@
+use plist::{Dictionary, Value};
+use std::fs;
+use std::path::Path;
+use sticky_situation::database::{AttachmentInfo, Database, Sticky};
+use sticky_situation::filesystem::{
+    self, plist::read_stickies_state, rtfd::Attachment, Frame, RtfdBundle,
+};
+use sticky_situation::layout;
+use sticky_situation::sync::{runner, shared, SyncAction, SyncOptions};
+use sticky_situation::version_vector::VersionVector;
+use tempfile::tempdir;
+
+fn write_state(stickies_path: &Path, entries: Vec<Dictionary>) {
+    let array = entries.into_iter().map(Value::Dictionary).collect();
+    plist::to_file_binary(
+        stickies_path.join(filesystem::SAVED_STATE_FILE),
+        &Value::Array(array),
+    )
+    .unwrap();
+}
+
+fn options(hostname: &str) -> SyncOptions {
+    SyncOptions {
+        dry_run: false,
+        hostname: hostname.to_string(),
+        machine_id: hostname.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_sync_persists_full_plist_metadata() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("ABC-123".into()));
+    entry.insert("Color".to_string(), Value::Integer(3.into()));
+    entry.insert(
+        "Frame".to_string(),
+        Value::String("{{40, 60}, {300, 200}}".into()),
+    );
+    entry.insert("Floating".to_string(), Value::Boolean(true));
+    entry.insert("FutureKey".to_string(), Value::Integer(7.into()));
+    write_state(&stickies_path, vec![entry.clone()]);
+
+    RtfdBundle::create_minimal("Metadata test")
+        .write(&filesystem::bundle_path(&stickies_path, "abc-123"))
+        .unwrap();
+
+    let report = runner::run(&db, &stickies_path, &options("machine-a")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::NewOnFilesystem("abc-123".to_string())]
+    );
+
+    let sticky = db.get_sticky("abc-123").unwrap().unwrap();
+    assert!(!sticky.plist_metadata.is_empty());
+    assert!(sticky.is_floating);
+    assert_eq!(sticky.color, "pink");
+
+    let stored = filesystem::StickyMetadata::from_bytes(&sticky.plist_metadata).unwrap();
+    assert_eq!(stored.raw, entry);
+}
+
+#[test]
+fn test_sync_writes_database_sticky_to_other_machine() {
+    let dir = tempdir().unwrap();
+    let machine_a = dir.path().join("machine-a");
+    let machine_b = dir.path().join("machine-b");
+    fs::create_dir(&machine_a).unwrap();
+    fs::create_dir(&machine_b).unwrap();
+    let db = Database::create(&dir.path().join("shared.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("ROUND-TRIP".into()));
+    entry.insert("Color".to_string(), Value::Integer(1.into()));
+    entry.insert(
+        "Frame".to_string(),
+        Value::String("{{500, 400}, {320, 240}}".into()),
+    );
+    entry.insert("FutureKey".to_string(), Value::String("survives".into()));
+    write_state(&machine_a, vec![entry]);
+
+    RtfdBundle {
+        rtf_data: b"{\\rtf1\\ansi Round trip}".to_vec(),
+        attachments: vec![Attachment {
+            filename: "image.png".to_string(),
+            content: b"fake png".to_vec(),
+        }],
+    }
+    .write(&filesystem::bundle_path(&machine_a, "round-trip"))
+    .unwrap();
+
+    let report = runner::run(&db, &machine_a, &options("machine-a")).unwrap();
+    // The RTF never mentions image.png, which sync reports but still copies
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("image.png"));
+
+    // Machine B has never seen the sticky
+    let report = runner::run(&db, &machine_b, &options("machine-b")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::NewInDatabase("round-trip".to_string())]
+    );
+
+    let bundle = RtfdBundle::read(&filesystem::bundle_path(&machine_b, "round-trip")).unwrap();
+    assert_eq!(bundle.rtf_data, b"{\\rtf1\\ansi Round trip}");
+    assert_eq!(bundle.attachments.len(), 1);
+    assert_eq!(bundle.attachments[0].content, b"fake png");
+
+    let state = read_stickies_state(&machine_b.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    let metadata = &state["round-trip"];
+    assert_eq!(metadata.frame.to_string(), "{{500, 400}, {320, 240}}");
+    assert_eq!(metadata.color_index, 1);
+    assert_eq!(
+        metadata.raw.get("FutureKey"),
+        Some(&Value::String("survives".into()))
+    );
+}
+
+#[test]
+fn test_dry_run_changes_nothing() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("DRY-RUN".into()));
+    write_state(&stickies_path, vec![entry]);
+    RtfdBundle::create_minimal("Dry run")
+        .write(&filesystem::bundle_path(&stickies_path, "dry-run"))
+        .unwrap();
+
+    let options = SyncOptions {
+        dry_run: true,
+        hostname: "test".to_string(),
+        ..Default::default()
+    };
+    let report = runner::run(&db, &stickies_path, &options).unwrap();
+
+    assert_eq!(report.changed(), 1);
+    assert!(db.get_all_uuids().unwrap().is_empty());
+}
+
+#[test]
+fn test_layout_only_change_updates_metadata() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("LAYOUT".into()));
+    entry.insert(
+        "Frame".to_string(),
+        Value::String("{{0, 0}, {200, 200}}".into()),
+    );
+    write_state(&stickies_path, vec![entry.clone()]);
+    RtfdBundle::create_minimal("Layout test")
+        .write(&filesystem::bundle_path(&stickies_path, "layout"))
+        .unwrap();
+
+    runner::run(&db, &stickies_path, &options("test")).unwrap();
+    let before = db.get_sticky("layout").unwrap().unwrap();
+
+    // The user drags the window; the bundle is untouched
+    entry.insert(
+        "Frame".to_string(),
+        Value::String("{{640, 480}, {200, 200}}".into()),
+    );
+    write_state(&stickies_path, vec![entry]);
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateLayout("layout".to_string())]
+    );
+
+    let after = db.get_sticky("layout").unwrap().unwrap();
+    assert_eq!(after.modified_at, before.modified_at);
+    let metadata = filesystem::StickyMetadata::from_bytes(&after.plist_metadata).unwrap();
+    assert_eq!(metadata.frame, Frame::new(640.0, 480.0, 200.0, 200.0));
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 0);
+}
+
+#[test]
+fn test_window_state_change_is_synced() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("STATE".into()));
+    write_state(&stickies_path, vec![entry.clone()]);
+    RtfdBundle::create_minimal("State test")
+        .write(&filesystem::bundle_path(&stickies_path, "state"))
+        .unwrap();
+
+    runner::run(&db, &stickies_path, &options("test")).unwrap();
+
+    // Collapsing and making a window translucent leaves its frame alone
+    entry.insert("Collapsed".to_string(), Value::Boolean(true));
+    entry.insert("Translucent".to_string(), Value::Boolean(true));
+    write_state(&stickies_path, vec![entry]);
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateLayout("state".to_string())]
+    );
+
+    let sticky = db.get_sticky("state").unwrap().unwrap();
+    let metadata = filesystem::StickyMetadata::from_bytes(&sticky.plist_metadata).unwrap();
+    assert!(metadata.is_collapsed);
+    assert!(metadata.is_translucent);
+}
+
+#[test]
+fn test_window_state_change_reaches_other_machine() {
+    let dir = tempdir().unwrap();
+    let laptop = dir.path().join("laptop");
+    let desktop = dir.path().join("desktop");
+    fs::create_dir(&laptop).unwrap();
+    fs::create_dir(&desktop).unwrap();
+    let db = Database::create(&dir.path().join("shared.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("STATE".into()));
+    write_state(&laptop, vec![entry.clone()]);
+    write_state(&desktop, vec![]);
+    RtfdBundle::create_minimal("State test")
+        .write(&filesystem::bundle_path(&laptop, "state"))
+        .unwrap();
+    runner::run(&db, &laptop, &options("laptop")).unwrap();
+    runner::run(&db, &desktop, &options("desktop")).unwrap();
+
+    // Collapsed on the laptop
+    entry.insert("Collapsed".to_string(), Value::Boolean(true));
+    write_state(&laptop, vec![entry]);
+    runner::run(&db, &laptop, &options("laptop")).unwrap();
+
+    // The desktop's state file is stale, so it takes the new state
+    let report = runner::run(&db, &desktop, &options("desktop")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateFilesystemLayout("state".to_string())]
+    );
+    let state = read_stickies_state(&desktop.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    assert!(state["state"].is_collapsed);
+
+    // Neither machine reverts it afterwards
+    for (path, machine) in [(&laptop, "laptop"), (&desktop, "desktop")] {
+        let report = runner::run(&db, path, &options(machine)).unwrap();
+        assert_eq!(report.changed(), 0, "{}", machine);
+    }
+    let sticky = db.get_sticky("state").unwrap().unwrap();
+    let metadata = filesystem::StickyMetadata::from_bytes(&sticky.plist_metadata).unwrap();
+    assert!(metadata.is_collapsed);
+}
+
+#[test]
+fn test_consecutive_syncs_after_export_are_no_change() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    // A sticky created on another machine well before this sync
+    db.insert_sticky(&Sticky {
+        uuid: "from-db".to_string(),
+        content_text: "Written elsewhere".to_string(),
+        rtf_data: RtfdBundle::create_minimal("Written elsewhere").rtf_data,
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "green".to_string(),
+        modified_at: 1_700_000_000,
+        created_at: 1_700_000_000,
+        source_machine: "machine-a".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+
+    let report = runner::run(&db, &stickies_path, &options("machine-b")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::NewInDatabase("from-db".to_string())]
+    );
+
+    let rtfd_path = filesystem::bundle_path(&stickies_path, "from-db");
+    assert_eq!(
+        RtfdBundle::modified_time(&rtfd_path).unwrap(),
+        1_700_000_000
+    );
+
+    for _ in 0..2 {
+        let report = runner::run(&db, &stickies_path, &options("machine-b")).unwrap();
+        assert_eq!(
+            report.actions,
+            vec![SyncAction::NoChange("from-db".to_string())]
+        );
+    }
+}
+
+#[test]
+fn test_touch_without_edit_is_no_change() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("TOUCHED".into()));
+    write_state(&stickies_path, vec![entry]);
+    let rtfd_path = filesystem::bundle_path(&stickies_path, "touched");
+    RtfdBundle::create_minimal("Same words")
+        .write(&rtfd_path)
+        .unwrap();
+    RtfdBundle::set_modified_time_ns(&rtfd_path, 1_700_000_000_000_000_100).unwrap();
+
+    runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(
+        db.get_sticky("touched").unwrap().unwrap().modified_ns,
+        1_700_000_000_000_000_100
+    );
+
+    // `touch` moves the mtime on without changing the content
+    RtfdBundle::set_modified_time_ns(&rtfd_path, 1_700_000_500_000_000_000).unwrap();
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 0);
+    // The touched bundle's hash is cached so later syncs don't read it again
+    assert_eq!(
+        db.get_scan_cache("test").unwrap()["touched"].modified_ns,
+        1_700_000_500_000_000_000
+    );
+
+    // A real edit within the same second is still picked up
+    RtfdBundle::create_minimal("New words")
+        .write(&rtfd_path)
+        .unwrap();
+    RtfdBundle::set_modified_time_ns(&rtfd_path, 1_700_000_000_000_000_200).unwrap();
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateDatabase("touched".to_string())]
+    );
+    assert!(db
+        .get_sticky("touched")
+        .unwrap()
+        .unwrap()
+        .content_text
+        .contains("New words"));
+}
+
+#[test]
+fn test_oversized_attachment_is_recorded_but_not_stored() {
+    let dir = tempdir().unwrap();
+    let machine_a = dir.path().join("machine-a");
+    let machine_b = dir.path().join("machine-b");
+    fs::create_dir(&machine_a).unwrap();
+    fs::create_dir(&machine_b).unwrap();
+    let db = Database::create(&dir.path().join("shared.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("BIG".into()));
+    write_state(&machine_a, vec![entry]);
+
+    let png = b"\x89PNG\r\n\x1a\nsmall".to_vec();
+    let recording = vec![0u8; 4096];
+    RtfdBundle {
+        rtf_data: b"{\\rtf1 Big}".to_vec(),
+        attachments: vec![
+            Attachment {
+                filename: "image.png".to_string(),
+                content: png.clone(),
+            },
+            Attachment {
+                filename: "recording.mov".to_string(),
+                content: recording.clone(),
+            },
+        ],
+    }
+    .write(&filesystem::bundle_path(&machine_a, "big"))
+    .unwrap();
+
+    let limited = SyncOptions {
+        max_attachment_bytes: 1024,
+        ..options("machine-a")
+    };
+    let report = runner::run(&db, &machine_a, &limited).unwrap();
+    assert!(report
+        .warnings
+        .iter()
+        .any(|w| w.contains("recording.mov") && w.contains("max_attachment_bytes")));
+
+    let stored = db.get_attachments("big").unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].filename, "image.png");
+
+    let info = db.get_attachment_info("big").unwrap();
+    let image = info.iter().find(|i| i.filename == "image.png").unwrap();
+    assert_eq!(image.mime_type, "image/png");
+    assert!(image.stored);
+    let skipped = info.iter().find(|i| i.filename == "recording.mov").unwrap();
+    assert_eq!(skipped.mime_type, "video/quicktime");
+    assert_eq!(skipped.size, 4096);
+    assert!(!skipped.stored);
+
+    // Another machine gets only what fits in the database
+    let limited_b = SyncOptions {
+        max_attachment_bytes: 1024,
+        ..options("machine-b")
+    };
+    runner::run(&db, &machine_b, &limited_b).unwrap();
+    let copy = RtfdBundle::read(&filesystem::bundle_path(&machine_b, "big")).unwrap();
+    assert_eq!(copy.attachments.len(), 1);
+
+    // Writing the note back to the original machine keeps its local recording
+    let mut sticky = db.get_sticky("big").unwrap().unwrap();
+    sticky.modified_ns += 1_000_000_000;
+    sticky.modified_at += 1;
+    sticky.content_hash = "edited elsewhere".to_string();
+    db.insert_sticky(&sticky).unwrap();
+    let report = runner::run(&db, &machine_a, &limited).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateFilesystem("big".to_string())]
+    );
+    let local = RtfdBundle::read(&filesystem::bundle_path(&machine_a, "big")).unwrap();
+    let names: Vec<&str> = local
+        .attachments
+        .iter()
+        .map(|a| a.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["image.png", "recording.mov"]);
+    assert_eq!(local.attachments[1].content, recording);
+
+    // An edit made where the recording never was doesn't drop its record...
+    let edited_ns = db.get_sticky("big").unwrap().unwrap().modified_ns + 1_000_000_000;
+    let path = filesystem::bundle_path(&machine_b, "big");
+    RtfdBundle {
+        rtf_data: b"{\\rtf1 Big, edited}".to_vec(),
+        attachments: vec![Attachment {
+            filename: "image.png".to_string(),
+            content: png.clone(),
+        }],
+    }
+    .write(&path)
+    .unwrap();
+    RtfdBundle::set_modified_time_ns(&path, edited_ns).unwrap();
+    let report = runner::run(&db, &machine_b, &limited_b).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateDatabase("big".to_string())]
+    );
+    let info = db.get_attachment_info("big").unwrap();
+    assert!(info
+        .iter()
+        .any(|i| i.filename == "recording.mov" && !i.stored));
+
+    // ...so the machine that has it keeps it when the edit arrives
+    let report = runner::run(&db, &machine_a, &limited).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateFilesystem("big".to_string())]
+    );
+    let local = RtfdBundle::read(&filesystem::bundle_path(&machine_a, "big")).unwrap();
+    assert!(String::from_utf8_lossy(&local.rtf_data).contains("edited"));
+    assert!(local
+        .attachments
+        .iter()
+        .any(|a| a.filename == "recording.mov" && a.content == recording));
+}
+
+#[test]
+fn test_reimported_new_sticky_is_rekeyed_not_duplicated() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    // `sticky new` stored this row, but Stickies.app imported the bundle
+    // under a UUID of its own
+    let bundle = RtfdBundle::create_minimal("Fresh note");
+    db.insert_sticky(&Sticky {
+        uuid: "ours".to_string(),
+        content_text: "Fresh note".to_string(),
+        rtf_data: bundle.rtf_data.clone(),
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1_700_000_000,
+        created_at: 1_700_000_000,
+        source_machine: "test".to_string(),
+        modified_ns: 1_700_000_000_000_000_000,
+        content_hash: bundle.content_hash(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+    db.add_tag("ours", "inbox").unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("THEIRS".into()));
+    write_state(&stickies_path, vec![entry]);
+    bundle
+        .write(&filesystem::bundle_path(&stickies_path, "theirs"))
+        .unwrap();
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::Rekey {
+            from: "ours".to_string(),
+            to: "theirs".to_string(),
+        }]
+    );
+
+    assert_eq!(db.get_all_uuids().unwrap(), vec!["theirs".to_string()]);
+    assert_eq!(db.get_tags("theirs").unwrap(), vec!["inbox".to_string()]);
+    assert_eq!(db.search("Fresh").unwrap()[0].uuid, "theirs");
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 0);
+}
+
+#[test]
+fn test_many_stickies_import_in_one_sync() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut entries = Vec::new();
+    for i in 0..64 {
+        let uuid = format!("NOTE-{:02}", i);
+        let mut entry = Dictionary::new();
+        entry.insert("UUID".to_string(), Value::String(uuid.clone()));
+        entries.push(entry);
+        RtfdBundle::create_minimal(&format!("Note number {} #bulk", i))
+            .write(&filesystem::bundle_path(
+                &stickies_path,
+                &uuid.to_lowercase(),
+            ))
+            .unwrap();
+    }
+    write_state(&stickies_path, entries);
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 64);
+    assert_eq!(db.get_all_uuids().unwrap().len(), 64);
+    assert!(db
+        .get_sticky("note-07")
+        .unwrap()
+        .unwrap()
+        .content_text
+        .contains("Note number 7"));
+    assert_eq!(db.get_tags("note-63").unwrap(), vec!["bulk"]);
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 0);
+}
+
+#[test]
+fn test_failed_sync_rolls_back_database_changes() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut moved = Dictionary::new();
+    moved.insert("UUID".to_string(), Value::String("MOVED".into()));
+    write_state(&stickies_path, vec![moved.clone()]);
+    RtfdBundle::create_minimal("Moved")
+        .write(&filesystem::bundle_path(&stickies_path, "moved"))
+        .unwrap();
+    runner::run(&db, &stickies_path, &options("test")).unwrap();
+
+    // One new note to import and one window moved, whose update will fail
+    let mut fresh = Dictionary::new();
+    fresh.insert("UUID".to_string(), Value::String("FRESH".into()));
+    moved.insert(
+        "Frame".to_string(),
+        Value::String("{{10, 10}, {200, 200}}".into()),
+    );
+    write_state(&stickies_path, vec![moved, fresh]);
+    RtfdBundle::create_minimal("Fresh")
+        .write(&filesystem::bundle_path(&stickies_path, "fresh"))
+        .unwrap();
+    db.connection()
+        .lock()
+        .unwrap()
+        .execute_batch(
+            "CREATE TRIGGER fail_layout BEFORE UPDATE OF plist_metadata ON stickies
+             BEGIN SELECT RAISE(ABORT, 'boom'); END",
+        )
+        .unwrap();
+
+    assert!(runner::run(&db, &stickies_path, &options("test")).is_err());
+    assert!(db.get_sticky("fresh").unwrap().is_none());
+
+    // Once the problem is gone the whole sync goes through
+    db.connection()
+        .lock()
+        .unwrap()
+        .execute_batch("DROP TRIGGER fail_layout")
+        .unwrap();
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 2);
+    assert!(db.get_sticky("fresh").unwrap().is_some());
+}
+
+#[test]
+fn test_interrupted_exports_are_resumed() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    write_state(&stickies_path, vec![]);
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let bundle = RtfdBundle::create_minimal("Committed but never written");
+    db.insert_sticky(&Sticky {
+        uuid: "pending".to_string(),
+        content_text: "Committed but never written".to_string(),
+        rtf_data: bundle.rtf_data.clone(),
+        plist_metadata: filesystem::StickyMetadata::new(0).to_bytes().unwrap(),
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1_700_000_000,
+        created_at: 1_700_000_000,
+        source_machine: "other".to_string(),
+        modified_ns: 1_700_000_000_000_000_000,
+        content_hash: bundle.content_hash(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+    // A previous run committed this export and stopped before writing it,
+    // along with a journal entry for a sticky that has since gone away
+    db.journal_exports("test", &["pending".to_string(), "gone".to_string()])
+        .unwrap();
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(report.changed(), 0);
+    assert!(filesystem::bundle_path(&stickies_path, "pending").exists());
+    let state = read_stickies_state(&stickies_path.join(filesystem::SAVED_STATE_FILE)).unwrap();
+    assert!(state.contains_key("pending"));
+    assert!(db.pending_exports("test").unwrap().is_empty());
+}
+
+#[test]
+fn test_unreadable_bundle_is_quarantined_and_the_rest_synced() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut good = Dictionary::new();
+    good.insert("UUID".to_string(), Value::String("GOOD".into()));
+    let mut broken = Dictionary::new();
+    broken.insert("UUID".to_string(), Value::String("BROKEN".into()));
+    write_state(&stickies_path, vec![good, broken]);
+    RtfdBundle::create_minimal("Fine")
+        .write(&filesystem::bundle_path(&stickies_path, "good"))
+        .unwrap();
+    // A bundle without TXT.rtf, holding only an image
+    let broken_path = filesystem::bundle_path(&stickies_path, "broken");
+    fs::create_dir(&broken_path).unwrap();
+    fs::write(broken_path.join("image.png"), b"png").unwrap();
+
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::NewOnFilesystem("good".to_string())]
+    );
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].uuid, "broken");
+    assert!(db.get_sticky("broken").unwrap().is_none());
+
+    let quarantine = db.get_quarantine("test").unwrap();
+    assert_eq!(quarantine.len(), 1);
+    assert_eq!(quarantine[0].uuid, "broken");
+    // Other machines keep their own quarantine
+    assert!(db.get_quarantine("other").unwrap().is_empty());
+
+    // Once repaired, the sticky syncs and leaves quarantine
+    RtfdBundle::create_minimal("Repaired")
+        .write(&broken_path)
+        .unwrap();
+    let report = runner::run(&db, &stickies_path, &options("test")).unwrap();
+    assert!(report.failures.is_empty());
+    assert!(db.get_sticky("broken").unwrap().is_some());
+    assert!(db.get_quarantine("test").unwrap().is_empty());
+}
+
+fn write_note(stickies_path: &Path, uuid: &str, text: &str, modified_ns: i64) {
+    let path = filesystem::bundle_path(stickies_path, uuid);
+    RtfdBundle::create_minimal(text).write(&path).unwrap();
+    RtfdBundle::set_modified_time_ns(&path, modified_ns).unwrap();
+}
+
+/// Two machines syncing one note through the same database
+fn two_machines(root: &Path) -> (Database, std::path::PathBuf, std::path::PathBuf) {
+    let laptop = root.join("laptop");
+    let desktop = root.join("desktop");
+    fs::create_dir(&laptop).unwrap();
+    fs::create_dir(&desktop).unwrap();
+    let db = Database::create(&root.join("shared.db")).unwrap();
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("NOTE".into()));
+    write_state(&laptop, vec![entry]);
+    write_state(&desktop, vec![]);
+    write_note(&laptop, "note", "Original", 1_700_000_000_000_000_000);
+    runner::run(&db, &laptop, &options("laptop")).unwrap();
+    runner::run(&db, &desktop, &options("desktop")).unwrap();
+    (db, laptop, desktop)
+}
+
+#[test]
+fn test_edit_from_machine_with_slow_clock_is_not_lost() {
+    let dir = tempdir().unwrap();
+    let (db, laptop, desktop) = two_machines(dir.path());
+
+    // The desktop's clock is an hour behind, so its edit looks older
+    write_note(&desktop, "note", "Desktop edit", 1_699_999_000_000_000_000);
+    let report = runner::run(&db, &desktop, &options("desktop")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateDatabase("note".to_string())]
+    );
+
+    // The laptop's untouched bundle is newer by the clock but older causally
+    let report = runner::run(&db, &laptop, &options("laptop")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateFilesystem("note".to_string())]
+    );
+    assert!(report.conflicts.is_empty());
+    let bundle = RtfdBundle::read(&filesystem::bundle_path(&laptop, "note")).unwrap();
+    assert!(String::from_utf8_lossy(&bundle.rtf_data).contains("Desktop edit"));
+
+    let sticky = db.get_sticky("note").unwrap().unwrap();
+    assert_eq!(sticky.version_vector.get("laptop"), 1);
+    assert_eq!(sticky.version_vector.get("desktop"), 1);
+}
+
+#[test]
+fn test_concurrent_edits_are_reported_as_conflicts() {
+    let dir = tempdir().unwrap();
+    let (db, laptop, desktop) = two_machines(dir.path());
+    let conflict_log = dir.path().join("conflicts.log");
+
+    write_note(&laptop, "note", "Laptop edit", 1_700_000_100_000_000_000);
+    write_note(&desktop, "note", "Desktop edit", 1_700_000_200_000_000_000);
+    let report = runner::run(&db, &laptop, &options("laptop")).unwrap();
+    assert!(report.conflicts.is_empty());
+
+    // Neither edit saw the other; the newer one is kept
+    let desktop_options = SyncOptions {
+        conflict_log: Some(conflict_log.clone()),
+        ..options("desktop")
+    };
+    let report = runner::run(&db, &desktop, &desktop_options).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateDatabase("note".to_string())]
+    );
+    assert_eq!(report.conflicts.len(), 1);
+    assert_eq!(report.conflicts[0].uuid, "note");
+    assert!(fs::read_to_string(&conflict_log).unwrap().contains("note"));
+
+    // The kept copy includes both histories, so the laptop simply takes it
+    let report = runner::run(&db, &laptop, &options("laptop")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::UpdateFilesystem("note".to_string())]
+    );
+    assert!(report.conflicts.is_empty());
+    let sticky = db.get_sticky("note").unwrap().unwrap();
+    assert!(sticky.content_text.contains("Desktop edit"));
+    assert_eq!(sticky.version_vector.get("laptop"), 2);
+    assert_eq!(sticky.version_vector.get("desktop"), 1);
+}
+
+#[test]
+fn test_set_frame_survives_syncs_from_other_machines() {
+    let dir = tempdir().unwrap();
+    let (db, laptop, desktop) = two_machines(dir.path());
+    let moved = Frame::new(40.0, 50.0, 300.0, 200.0);
+
+    // `sticky set` on the laptop, then `sticky set` with no local Stickies
+    layout::set_frame(
+        &db,
+        Some(&laptop),
+        "laptop",
+        "note",
+        Some((300.0, 200.0)),
+        None,
+    )
+    .unwrap();
+    let (frame, local) =
+        layout::set_frame(&db, None, "desktop", "note", None, Some((40.0, 50.0))).unwrap();
+    assert_eq!(frame, moved);
+    assert!(!local);
+
+    // Both state files are stale, so both machines take the new frame
+    for (path, machine) in [(&desktop, "desktop"), (&laptop, "laptop")] {
+        let report = runner::run(&db, path, &options(machine)).unwrap();
+        assert_eq!(
+            report.actions,
+            vec![SyncAction::UpdateFilesystemLayout("note".to_string())],
+            "{}",
+            machine
+        );
+        let state = read_stickies_state(&path.join(filesystem::SAVED_STATE_FILE)).unwrap();
+        assert_eq!(state["note"].frame, moved);
+    }
+
+    for (path, machine) in [(&desktop, "desktop"), (&laptop, "laptop")] {
+        let report = runner::run(&db, path, &options(machine)).unwrap();
+        assert_eq!(report.changed(), 0, "{}", machine);
+    }
+    let sticky = db.get_sticky("note").unwrap().unwrap();
+    let metadata = filesystem::StickyMetadata::from_bytes(&sticky.plist_metadata).unwrap();
+    assert_eq!(metadata.frame, moved);
+}
+
+#[test]
+fn test_hostname_change_keeps_machine_identity() {
+    let dir = tempdir().unwrap();
+    let stickies_path = dir.path().join("Stickies");
+    let shared_dir = dir.path().join("iCloud");
+    fs::create_dir(&stickies_path).unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+    let renamed = |hostname: &str| SyncOptions {
+        shared_dir: Some(shared_dir.clone()),
+        machine_id: "laptop-id".to_string(),
+        ..options(hostname)
+    };
+
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("FIRST".into()));
+    write_state(&stickies_path, vec![entry]);
+    write_note(&stickies_path, "first", "Mine", 1_700_000_000_000_000_000);
+    runner::run(&db, &stickies_path, &renamed("old-name")).unwrap();
+    let sticky = db.get_sticky("first").unwrap().unwrap();
+    assert_eq!(sticky.source_machine, "laptop-id");
+
+    // Stickies.app re-keys the note while the machine is renamed
+    let mut entry = Dictionary::new();
+    entry.insert("UUID".to_string(), Value::String("SECOND".into()));
+    write_state(&stickies_path, vec![entry]);
+    fs::rename(
+        filesystem::bundle_path(&stickies_path, "first"),
+        filesystem::bundle_path(&stickies_path, "second"),
+    )
+    .unwrap();
+
+    // Still recognized as this machine's note, and logged to the same file
+    let report = runner::run(&db, &stickies_path, &renamed("new-name")).unwrap();
+    assert_eq!(
+        report.actions,
+        vec![SyncAction::Rekey {
+            from: "first".to_string(),
+            to: "second".to_string(),
+        }]
+    );
+    let logs: Vec<_> = fs::read_dir(shared_dir.join(shared::CHANGES_DIR))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(logs, vec!["laptop-id.ndjson"]);
+}
+
+#[test]
+fn test_unsafe_local_only_attachment_names_are_not_exported() {
+    let dir = tempdir().unwrap();
+    let (db, laptop, desktop) = two_machines(dir.path());
+    let secret = dir.path().join("secret.txt");
+    fs::write(&secret, "private").unwrap();
+
+    // A record naming a file outside the bundle, as a tampered change log or
+    // merged database could carry
+    let info = AttachmentInfo {
+        filename: secret.to_string_lossy().to_string(),
+        mime_type: "text/plain".to_string(),
+        size: 7,
+        stored: false,
+    };
+    db.save_attachments_with_skipped("note", &[], &[info])
+        .unwrap();
+
+    write_note(&laptop, "note", "Edited", 1_800_000_000_000_000_000);
+    runner::run(&db, &laptop, &options("laptop")).unwrap();
+    let report = runner::run(&db, &desktop, &options("desktop")).unwrap();
+    assert!(report.failures.is_empty(), "{:?}", report.failures);
+
+    let bundle = RtfdBundle::read(&filesystem::bundle_path(&desktop, "note")).unwrap();
+    assert!(String::from_utf8_lossy(&bundle.rtf_data).contains("Edited"));
+    assert!(bundle.attachments.is_empty());
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use sticky_situation::sync::{SyncAction, SyncEngine};
+use sticky_situation::sync::{SyncAction, SyncEngine, Version};
 
 #[test]
 fn test_categorize_new_on_filesystem() {
@@ -114,3 +114,184 @@ fn test_categorize_mixed_actions() {
     // Verify we have exactly 5 actions
     assert_eq!(actions.len(), 5);
 }
+
+#[test]
+fn test_detect_layout_changes() {
+    let actions = vec![
+        SyncAction::NoChange("moved".to_string()),
+        SyncAction::NoChange("still".to_string()),
+        SyncAction::UpdateDatabase("edited".to_string()),
+    ];
+
+    let db_layouts = HashMap::from([
+        ("moved".to_string(), (0, 0)),
+        ("still".to_string(), (5, 5)),
+        ("edited".to_string(), (1, 1)),
+    ]);
+    let fs_layouts = HashMap::from([
+        ("moved".to_string(), (10, 10)),
+        ("still".to_string(), (5, 5)),
+        ("edited".to_string(), (2, 2)),
+    ]);
+
+    let actions = SyncEngine::detect_layout_changes(actions, &db_layouts, &fs_layouts);
+
+    assert_eq!(
+        actions,
+        vec![
+            SyncAction::UpdateLayout("moved".to_string()),
+            SyncAction::NoChange("still".to_string()),
+            SyncAction::UpdateDatabase("edited".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_direct_layout_changes() {
+    let actions = vec![
+        SyncAction::UpdateLayout("moved-here".to_string()),
+        SyncAction::UpdateLayout("moved-elsewhere".to_string()),
+        SyncAction::UpdateLayout("never-synced".to_string()),
+    ];
+
+    let db_layouts = HashMap::from([
+        ("moved-here".to_string(), (0, 0)),
+        ("moved-elsewhere".to_string(), (9, 9)),
+        ("never-synced".to_string(), (0, 0)),
+    ]);
+    let fs_layouts = HashMap::from([
+        ("moved-here".to_string(), (5, 5)),
+        ("moved-elsewhere".to_string(), (0, 0)),
+        ("never-synced".to_string(), (5, 5)),
+    ]);
+    let synced_layouts = HashMap::from([
+        ("moved-here".to_string(), (0, 0)),
+        ("moved-elsewhere".to_string(), (0, 0)),
+    ]);
+
+    let actions =
+        SyncEngine::direct_layout_changes(actions, &db_layouts, &fs_layouts, &synced_layouts);
+
+    assert_eq!(
+        actions,
+        vec![
+            SyncAction::UpdateLayout("moved-here".to_string()),
+            SyncAction::UpdateFilesystemLayout("moved-elsewhere".to_string()),
+            SyncAction::UpdateLayout("never-synced".to_string()),
+        ]
+    );
+}
+
+fn version(modified_ns: i64, content_hash: &str) -> Version {
+    Version {
+        modified_ns,
+        content_hash: content_hash.to_string(),
+    }
+}
+
+#[test]
+fn test_categorize_versions_ignores_touch() {
+    let fs_uuids = vec!["uuid-1".to_string()];
+    let db = HashMap::from([("uuid-1".to_string(), version(1_000, "abc"))]);
+    let fs = HashMap::from([("uuid-1".to_string(), version(9_000, "abc"))]);
+
+    let actions = SyncEngine::categorize_versions(&fs_uuids, &db, &fs);
+    assert_eq!(actions, vec![SyncAction::NoChange("uuid-1".to_string())]);
+}
+
+#[test]
+fn test_categorize_versions_orders_changes_within_a_second() {
+    let fs_uuids = vec!["uuid-1".to_string()];
+    let db = HashMap::from([("uuid-1".to_string(), version(5_000_000_100, "old"))]);
+
+    let fs = HashMap::from([("uuid-1".to_string(), version(5_000_000_200, "new"))]);
+    let actions = SyncEngine::categorize_versions(&fs_uuids, &db, &fs);
+    assert_eq!(
+        actions,
+        vec![SyncAction::UpdateDatabase("uuid-1".to_string())]
+    );
+
+    let fs = HashMap::from([("uuid-1".to_string(), version(5_000_000_050, "new"))]);
+    let actions = SyncEngine::categorize_versions(&fs_uuids, &db, &fs);
+    assert_eq!(
+        actions,
+        vec![SyncAction::UpdateFilesystem("uuid-1".to_string())]
+    );
+}
+
+#[test]
+fn test_categorize_versions_without_hash_uses_timestamps() {
+    let fs_uuids = vec!["uuid-1".to_string(), "uuid-2".to_string()];
+    let db = HashMap::from([
+        ("uuid-1".to_string(), version(1_000, "")),
+        ("uuid-3".to_string(), version(1_000, "abc")),
+    ]);
+    let fs = HashMap::from([
+        ("uuid-1".to_string(), version(1_000, "abc")),
+        ("uuid-2".to_string(), version(1_000, "abc")),
+    ]);
+
+    let actions = SyncEngine::categorize_versions(&fs_uuids, &db, &fs);
+    assert_eq!(
+        actions,
+        vec![
+            SyncAction::NoChange("uuid-1".to_string()),
+            SyncAction::NewOnFilesystem("uuid-2".to_string()),
+            SyncAction::NewInDatabase("uuid-3".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_reconcile_rekeys_pairs_matching_content() {
+    let actions = vec![
+        SyncAction::NewOnFilesystem("stickies-uuid".to_string()),
+        SyncAction::NewOnFilesystem("unrelated".to_string()),
+        SyncAction::NewInDatabase("our-uuid".to_string()),
+        SyncAction::NewInDatabase("other-machine".to_string()),
+    ];
+    let candidates = HashMap::from([("our-uuid".to_string(), version(1, "same"))]);
+    let fs = HashMap::from([
+        ("stickies-uuid".to_string(), version(2, "same")),
+        ("unrelated".to_string(), version(2, "different")),
+    ]);
+
+    let actions = SyncEngine::reconcile_rekeys(actions, &candidates, &fs);
+    assert_eq!(
+        actions,
+        vec![
+            SyncAction::Rekey {
+                from: "our-uuid".to_string(),
+                to: "stickies-uuid".to_string(),
+            },
+            SyncAction::NewOnFilesystem("unrelated".to_string()),
+            SyncAction::NewInDatabase("other-machine".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_reconcile_rekeys_matches_each_row_once() {
+    let actions = vec![
+        SyncAction::NewOnFilesystem("fs-1".to_string()),
+        SyncAction::NewOnFilesystem("fs-2".to_string()),
+        SyncAction::NewInDatabase("db-1".to_string()),
+    ];
+    let candidates = HashMap::from([("db-1".to_string(), version(1, "same"))]);
+    let fs = HashMap::from([
+        ("fs-1".to_string(), version(2, "same")),
+        ("fs-2".to_string(), version(2, "same")),
+    ]);
+
+    let actions = SyncEngine::reconcile_rekeys(actions, &candidates, &fs);
+    assert_eq!(
+        actions,
+        vec![
+            SyncAction::Rekey {
+                from: "db-1".to_string(),
+                to: "fs-1".to_string(),
+            },
+            SyncAction::NewOnFilesystem("fs-2".to_string()),
+        ]
+    );
+}
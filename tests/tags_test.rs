@@ -0,0 +1,148 @@
+use sticky_situation::database::{Database, Sticky};
+use sticky_situation::query::SearchQuery;
+use sticky_situation::tags::{extract_hashtags, normalize_tag};
+use sticky_situation::version_vector::VersionVector;
+use tempfile::tempdir;
+
+#[test]
+fn test_extract_hashtags() {
+    let tags = extract_hashtags("Plan #Work trip, see #travel and #work again");
+    assert_eq!(tags, vec!["travel".to_string(), "work".to_string()]);
+}
+
+#[test]
+fn test_extract_hashtags_ignores_non_tags() {
+    assert!(extract_hashtags("Learning C# today").is_empty());
+    assert!(extract_hashtags("Issue #1234 and &#123;").is_empty());
+    assert!(extract_hashtags("no tags here").is_empty());
+}
+
+#[test]
+fn test_normalize_tag() {
+    assert_eq!(normalize_tag("#Work"), Some("work".to_string()));
+    assert_eq!(normalize_tag("follow-up"), Some("follow-up".to_string()));
+    assert_eq!(normalize_tag(""), None);
+    assert_eq!(normalize_tag("two words"), None);
+}
+
+#[test]
+fn test_hashtags_indexed_on_insert() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let mut sticky = Sticky {
+        uuid: "tagged".to_string(),
+        content_text: "Call the bank #finance #todo".to_string(),
+        rtf_data: vec![],
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1000,
+        created_at: 1000,
+        source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
+    };
+    db.insert_sticky(&sticky).unwrap();
+    assert_eq!(db.get_tags("tagged").unwrap(), vec!["finance", "todo"]);
+
+    // Editing the text refreshes hashtag tags
+    sticky.content_text = "Called the bank #finance".to_string();
+    sticky.modified_at = 2000;
+    db.insert_sticky(&sticky).unwrap();
+    assert_eq!(db.get_tags("tagged").unwrap(), vec!["finance"]);
+}
+
+#[test]
+fn test_manual_tags_survive_resync() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    let sticky = Sticky {
+        uuid: "manual".to_string(),
+        content_text: "Quarterly numbers".to_string(),
+        rtf_data: vec![],
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1000,
+        created_at: 1000,
+        source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
+    };
+    db.insert_sticky(&sticky).unwrap();
+    db.add_tag("manual", "work").unwrap();
+
+    // A sync re-inserts the sticky from the filesystem
+    db.insert_sticky(&sticky).unwrap();
+    assert_eq!(db.get_tags("manual").unwrap(), vec!["work"]);
+
+    assert!(!db.remove_tag("manual", "work").unwrap());
+    assert!(db.get_tags("manual").unwrap().is_empty());
+}
+
+#[test]
+fn test_remove_tag_reports_hashtag_source() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    db.insert_sticky(&Sticky {
+        uuid: "both".to_string(),
+        content_text: "Dentist #personal".to_string(),
+        rtf_data: vec![],
+        plist_metadata: vec![],
+        is_floating: false,
+        color: "yellow".to_string(),
+        modified_at: 1000,
+        created_at: 1000,
+        source_machine: "test".to_string(),
+        modified_ns: 0,
+        content_hash: String::new(),
+        version_vector: VersionVector::new(),
+    })
+    .unwrap();
+    db.add_tag("both", "personal").unwrap();
+
+    assert!(db.remove_tag("both", "personal").unwrap());
+    assert_eq!(db.get_tags("both").unwrap(), vec!["personal"]);
+}
+
+#[test]
+fn test_tag_filter_in_search() {
+    let dir = tempdir().unwrap();
+    let db = Database::create(&dir.path().join("test.db")).unwrap();
+
+    for (uuid, content) in [("a", "Standup notes #work"), ("b", "Standup for kids club")] {
+        db.insert_sticky(&Sticky {
+            uuid: uuid.to_string(),
+            content_text: content.to_string(),
+            rtf_data: vec![],
+            plist_metadata: vec![],
+            is_floating: false,
+            color: "yellow".to_string(),
+            modified_at: 1000,
+            created_at: 1000,
+            source_machine: "test".to_string(),
+            modified_ns: 0,
+            content_hash: String::new(),
+            version_vector: VersionVector::new(),
+        })
+        .unwrap();
+    }
+    db.add_tag("b", "personal").unwrap();
+
+    let search = |q: &str| -> Vec<String> {
+        db.search_query(&SearchQuery::parse(q).unwrap(), None, 0)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.sticky.uuid)
+            .collect()
+    };
+
+    assert_eq!(search("standup tag:work"), vec!["a"]);
+    assert_eq!(search("tag:personal"), vec!["b"]);
+    assert_eq!(search("standup -tag:work"), vec!["b"]);
+}
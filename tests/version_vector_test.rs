@@ -0,0 +1,53 @@
+// ABOUTME: Tests for version vectors
+// ABOUTME: Verifies causal ordering, merging and the stored JSON form
+
+use sticky_situation::version_vector::{Causality, VersionVector};
+
+fn vector(counts: &[(&str, u64)]) -> VersionVector {
+    let mut vector = VersionVector::new();
+    for (machine, count) in counts {
+        for _ in 0..*count {
+            vector.increment(machine);
+        }
+    }
+    vector
+}
+
+#[test]
+fn test_compare() {
+    let base = vector(&[("laptop", 1)]);
+    let edited = vector(&[("laptop", 1), ("desktop", 1)]);
+    let other_edit = vector(&[("laptop", 2)]);
+
+    assert_eq!(base.compare(&base.clone()), Causality::Equal);
+    assert_eq!(base.compare(&edited), Causality::Before);
+    assert_eq!(edited.compare(&base), Causality::After);
+    assert_eq!(edited.compare(&other_edit), Causality::Concurrent);
+    assert_eq!(VersionVector::new().compare(&base), Causality::Before);
+}
+
+#[test]
+fn test_merged_includes_both_histories() {
+    let a = vector(&[("laptop", 3), ("desktop", 1)]);
+    let b = vector(&[("laptop", 1), ("desktop", 2), ("ipad", 1)]);
+    let merged = a.merged(&b);
+
+    assert_eq!(
+        merged,
+        vector(&[("laptop", 3), ("desktop", 2), ("ipad", 1)])
+    );
+    assert_eq!(merged.compare(&a), Causality::After);
+    assert_eq!(merged.compare(&b), Causality::After);
+}
+
+#[test]
+fn test_json_roundtrip() {
+    let original = vector(&[("laptop", 2), ("desktop", 1)]);
+    let json = original.to_json();
+    assert_eq!(json, r#"{"desktop":1,"laptop":2}"#);
+    assert_eq!(VersionVector::from_json(&json), original);
+
+    // Rows from before vectors existed have an empty column
+    assert!(VersionVector::from_json("").is_empty());
+    assert!(VersionVector::from_json("not json").is_empty());
+}
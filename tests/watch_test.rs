@@ -0,0 +1,43 @@
+// ABOUTME: Tests for watch mode helpers
+// ABOUTME: Verifies event filtering and database change detection
+
+use std::path::Path;
+use sticky_situation::sync::watch::{is_relevant, DatabaseStamp};
+use tempfile::TempDir;
+
+#[test]
+fn test_is_relevant_ignores_our_temporary_files() {
+    assert!(is_relevant(Path::new("/s/ABC.rtfd/TXT.rtf")));
+    assert!(is_relevant(Path::new("/s/.SavedStickiesState")));
+    assert!(!is_relevant(Path::new("/s/.ABC.rtfd.tmp-42/TXT.rtf")));
+    assert!(!is_relevant(Path::new("/s/.ABC.rtfd.old-42")));
+    assert!(!is_relevant(Path::new("/s/.SavedStickiesState.tmp")));
+}
+
+#[test]
+fn test_database_stamp_changes_when_file_written() {
+    let temp = TempDir::new().unwrap();
+    let db_path = temp.path().join("stickies.db");
+
+    let missing = DatabaseStamp::read(&db_path, None);
+    std::fs::write(&db_path, b"one").unwrap();
+    let written = DatabaseStamp::read(&db_path, None);
+    assert_ne!(missing, written);
+    assert_eq!(written, DatabaseStamp::read(&db_path, None));
+
+    std::fs::write(&db_path, b"longer").unwrap();
+    assert_ne!(written, DatabaseStamp::read(&db_path, None));
+}
+
+#[test]
+fn test_database_stamp_includes_shared_change_logs() {
+    let temp = TempDir::new().unwrap();
+    let db_path = temp.path().join("stickies.db");
+    let shared = temp.path().join("shared");
+    std::fs::write(&db_path, b"db").unwrap();
+
+    let before = DatabaseStamp::read(&db_path, Some(&shared));
+    std::fs::create_dir_all(shared.join("changes")).unwrap();
+    std::fs::write(shared.join("changes/desktop.ndjson"), b"{}\n").unwrap();
+    assert_ne!(before, DatabaseStamp::read(&db_path, Some(&shared)));
+}